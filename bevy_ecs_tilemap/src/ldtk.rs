@@ -1,7 +1,7 @@
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
 use crate::prelude::*;
 
-use bevy::{asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset}, prelude::*};
+use bevy::{asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset}, ecs::{component::Component, system::EntityCommands}, prelude::*};
 use bevy::reflect::TypeUuid;
 
 #[derive(TypeUuid)]
@@ -9,11 +9,128 @@ use bevy::reflect::TypeUuid;
 pub struct LdtkMap {
     pub project: ldtk_rust::Project,
     pub tilesets: HashMap<i64, Handle<Texture>>,
+    /// Parallax factors by layer definition uid, read from the project file as ldtk_rust doesn't
+    /// know about them yet. Only LDtk 1.0 projects have them.
+    pub layer_parallax: HashMap<i64, Vec2>,
+    /// Instance ids of the project's levels in order, read from the project file like the
+    /// parallax factors. `None` for levels saved by LDtk versions before 1.0.
+    pub level_iids: Vec<Option<String>>,
 }
 
+impl LdtkMap {
+    /// Reads a project file, leaving the tilesets for the loader to fill in.
+    fn from_json(raw_project: serde_json::Value) -> Result<Self, serde_json::Error> {
+        let layer_parallax = raw_project["defs"]["layers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|layer| {
+                let factor = |key: &str| layer.get(key).and_then(|value| value.as_f64()).unwrap_or(0.0) as f32;
+                Some((layer.get("uid")?.as_i64()?, Vec2::new(factor("parallaxFactorX"), factor("parallaxFactorY"))))
+            })
+            .collect();
+        let level_iids = raw_project["levels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|level| level.get("iid").and_then(|iid| iid.as_str()).map(str::to_string))
+            .collect();
+        Ok(LdtkMap {
+            project: serde_json::from_value(raw_project)?,
+            tilesets: HashMap::default(),
+            layer_parallax,
+            level_iids,
+        })
+    }
+
+    /// Position in pixels of a level in the project's world layout, y up. Levels of linear
+    /// layouts are placed next to each other in order.
+    pub fn level_offset(&self, index: usize) -> Vec2 {
+        let levels = &self.project.levels;
+        match self.project.world_layout {
+            ldtk_rust::WorldLayout::Free | ldtk_rust::WorldLayout::GridVania => {
+                Vec2::new(levels[index].world_x as f32, -levels[index].world_y as f32)
+            }
+            ldtk_rust::WorldLayout::LinearHorizontal => {
+                Vec2::new(levels[..index].iter().map(|level| level.px_wid).sum::<i64>() as f32, 0.0)
+            }
+            ldtk_rust::WorldLayout::LinearVertical => {
+                Vec2::new(0.0, -levels[..index].iter().map(|level| level.px_hei).sum::<i64>() as f32)
+            }
+        }
+    }
+
+    /// Bottom left and top right corners in pixels of the tiles of a level, relative to its map.
+    /// Levels not a multiple of the grid size in size are rounded up to whole cells.
+    pub fn level_bounds(&self, index: usize) -> (Vec2, Vec2) {
+        let level = &self.project.levels[index];
+        let grid_size = self.project.default_grid_size;
+        let whole_cells = |px: i64| ((px + grid_size - 1) / grid_size * grid_size) as f32;
+        // the tiles are moved up a row, see `process_loaded_tile_maps`
+        let top = ((level.px_hei / grid_size + 1) * grid_size - level.px_hei) as f32;
+        (Vec2::new(0.0, top - whole_cells(level.px_hei)), Vec2::new(whole_cells(level.px_wid), top))
+    }
+
+    /// Translation of a level's map that puts the top left corner of the level at its
+    /// `level_offset`.
+    pub fn level_map_offset(&self, index: usize) -> Vec2 {
+        let (_, max) = self.level_bounds(index);
+        self.level_offset(index) - Vec2::new(0.0, max.y)
+    }
+}
+
+/// The level of a project an `LdtkMapBundle` shows. Changing it respawns the map with the newly
+/// selected level.
 #[derive(Default)]
 pub struct LdtkMapConfig {
-    pub selected_level: usize,
+    pub selected_level: LevelSelection,
+}
+
+/// Picks a level of a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelSelection {
+    /// Position in the project's levels.
+    Index(usize),
+    /// The level's name in LDtk.
+    Identifier(String),
+    /// The level's unique id.
+    Uid(i64),
+    /// The level's instance id, only found in LDtk 1.0 projects.
+    Iid(String),
+}
+
+impl Default for LevelSelection {
+    fn default() -> Self {
+        LevelSelection::Index(0)
+    }
+}
+
+impl LevelSelection {
+    /// Index of the selected level in the project's levels, `None` if there is no such level.
+    pub fn index(&self, ldtk_map: &LdtkMap) -> Option<usize> {
+        let project = &ldtk_map.project;
+        match self {
+            LevelSelection::Index(index) => Some(*index).filter(|index| *index < project.levels.len()),
+            LevelSelection::Identifier(identifier) => {
+                project.levels.iter().position(|level| &level.identifier == identifier)
+            }
+            LevelSelection::Uid(uid) => project.levels.iter().position(|level| level.uid == *uid),
+            LevelSelection::Iid(iid) => ldtk_map.level_iids.iter().position(|level_iid| level_iid.as_ref() == Some(iid)),
+        }
+    }
+}
+
+/// Sent once the loader has spawned the layers, tiles and entities of a level. They are
+/// spawned through `Commands`, so they can be queried by systems in later stages, or in the
+/// next frame.
+#[derive(Debug, Clone)]
+pub struct LdtkLevelSpawned {
+    /// The map entity the level was spawned for.
+    pub map: Entity,
+    pub map_id: u16,
+    /// Index in the project's levels.
+    pub level: usize,
+    pub identifier: String,
 }
 
 #[derive(Default, Bundle)]
@@ -25,6 +142,223 @@ pub struct LdtkMapBundle {
     pub ldtk_map_config: LdtkMapConfig,
 }
 
+/// The LDtk properties of a layer, added to the layer entities the loader spawns.
+pub struct LdtkLayer {
+    pub identifier: String,
+    /// Id of the map layer the layer was loaded as.
+    pub layer_id: u16,
+    /// Already applied to the alpha of every tile.
+    pub opacity: f32,
+    /// Hidden layers are spawned with invisible chunks, their tiles can still be used for
+    /// gameplay.
+    pub visible: bool,
+    /// Total offset in pixels, y up, already applied to the layer's transform and entities.
+    pub offset: Vec2,
+    /// How much the layer follows the `LdtkParallaxCamera`, zero for no parallax.
+    pub parallax: Vec2,
+    /// Translation of the layer with the camera at `parallax_anchor`.
+    base_translation: Vec3,
+    /// World position of the level's center, where parallax layers line up with the others.
+    parallax_anchor: Vec2,
+}
+
+/// Marks the camera parallax layers are scrolled relative to.
+pub struct LdtkParallaxCamera;
+
+/// Spawns several levels of an LDtk project at once, at their positions in the project's world
+/// layout.
+///
+/// Every level in `levels` is spawned as a map of its own with an `LdtkMapBundle` and an
+/// `LdtkLevel`, and despawned again along with its entities once it is taken out. Level maps
+/// are not parented to the world, their transforms are the world's transform moved by the
+/// level's `LdtkMap::level_map_offset`, so the top left corners of the levels are at their
+/// offsets in the world.
+#[derive(Default)]
+pub struct LdtkWorld {
+    /// Map id of the first level, the others get the ids following it in level order.
+    pub first_map_id: u16,
+    /// Indices of the levels to spawn, kept up to date by `LdtkWorldFocus` if there is one.
+    pub levels: HashSet<usize>,
+    spawned: HashMap<usize, Entity>,
+}
+
+impl LdtkWorld {
+    pub fn new(first_map_id: u16, levels: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            first_map_id,
+            levels: levels.into_iter().collect(),
+            spawned: HashMap::new(),
+        }
+    }
+
+    /// Map id of a level.
+    pub fn map_id(&self, index: usize) -> u16 {
+        self.first_map_id + index as u16
+    }
+
+    /// The map entity of a spawned level.
+    pub fn level_entity(&self, index: usize) -> Option<Entity> {
+        self.spawned.get(&index).copied()
+    }
+}
+
+#[derive(Default, Bundle)]
+pub struct LdtkWorldBundle {
+    pub ldtk_map: Handle<LdtkMap>,
+    pub world: LdtkWorld,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+/// Added to the map entity of every level spawned by an `LdtkWorld`.
+pub struct LdtkLevel {
+    pub identifier: String,
+    /// Index in the project's levels.
+    pub index: usize,
+    /// The world entity the level belongs to.
+    pub world: Entity,
+}
+
+/// Loads the levels of every `LdtkWorld` that are within `load_distance` pixels of this
+/// entity, usually the camera, and unloads the others.
+pub struct LdtkWorldFocus {
+    pub load_distance: f32,
+}
+
+/// An entity instance from an LDtk `Entities` layer.
+///
+/// The loader spawns one ECS entity with this component for every entity instance in the
+/// selected level, with a `Transform` at the center of the instance in world space. Entities
+/// are not parented to the map, so they are left alone when the map is despawned.
+pub struct LdtkEntity {
+    pub identifier: String,
+    /// Cell of the instance's pivot, counted from the top left of the level like in LDtk.
+    pub grid: IVec2,
+    /// Position of the instance's pivot in pixels, counted from the top left of the level.
+    pub px: IVec2,
+    /// Size in pixels.
+    pub size: Vec2,
+    pub pivot: Vec2,
+    /// Size of a grid cell in pixels.
+    pub grid_size: f32,
+    /// The tile LDtk shows the instance with, if any.
+    pub tile: Option<LdtkEntityTile>,
+    /// Field values by field identifier, `Null` for fields left empty.
+    pub fields: HashMap<String, serde_json::Value>,
+    /// The map entity the instance was loaded for.
+    pub map: Entity,
+}
+
+/// A rectangle of a tileset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LdtkEntityTile {
+    pub tileset_uid: i64,
+    /// x, y, width and height in pixels.
+    pub src_rect: [i64; 4],
+}
+
+impl LdtkEntity {
+    /// The value of a `Float` or `Int` field.
+    pub fn field_f32(&self, identifier: &str) -> Option<f32> {
+        self.fields.get(identifier)?.as_f64().map(|value| value as f32)
+    }
+
+    /// The cells of a `Point` or `Array<Point>` field, empty if the field is missing or empty.
+    pub fn field_points(&self, identifier: &str) -> Vec<IVec2> {
+        let point = |value: &serde_json::Value| {
+            Some(IVec2::new(value.get("cx")?.as_i64()? as i32, value.get("cy")?.as_i64()? as i32))
+        };
+        match self.fields.get(identifier) {
+            Some(serde_json::Value::Array(values)) => values.iter().filter_map(point).collect(),
+            Some(value) => point(value).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// World space offset from the instance's own cell to `cell`, such as a point field.
+    pub fn cell_offset(&self, cell: IVec2) -> Vec2 {
+        let offset = (cell - self.grid).as_f32() * self.grid_size;
+        // LDtk counts rows down from the top
+        Vec2::new(offset.x, -offset.y)
+    }
+}
+
+/// The values of an LDtk `IntGrid` layer, such as collision data.
+///
+/// The loader spawns an entity with this component for every IntGrid layer of the selected
+/// level, as a child of the map. Auto-layer tiles generated from the grid are rendered on the
+/// map layer with the same id, like the tiles of any other layer.
+pub struct LdtkIntGrid {
+    pub identifier: String,
+    /// Id of the map layer the grid was loaded as.
+    pub layer_id: u16,
+    /// Size in cells.
+    pub size: UVec2,
+    /// Rows of the map's tiles, which are flipped by this instead of `size.y` as the level height
+    /// is rounded down to whole tiles.
+    pub rows: u32,
+    /// Size of a cell in pixels.
+    pub grid_size: f32,
+    /// Values left to right and top to bottom, `0` for empty cells.
+    pub values: Vec<i64>,
+    /// The map entity the grid was loaded for.
+    pub map: Entity,
+}
+
+impl LdtkIntGrid {
+    /// The value of a cell counted from the top left of the level like in LDtk, `0` outside the
+    /// grid.
+    pub fn get_cell(&self, cell: IVec2) -> i64 {
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.size.x as i32 || cell.y >= self.size.y as i32 {
+            return 0;
+        }
+        self.values[(cell.y * self.size.x as i32 + cell.x) as usize]
+    }
+
+    /// The value at a tile position of the map's layers, `0` outside the grid.
+    pub fn get(&self, tile_pos: UVec2) -> i64 {
+        // the same flip as the tiles, which are moved up a row
+        self.get_cell(IVec2::new(tile_pos.x as i32, self.rows as i32 - tile_pos.y as i32))
+    }
+}
+
+type LdtkEntitySpawner = Box<dyn Fn(&mut EntityCommands, &LdtkEntity) + Send + Sync>;
+
+/// Components to add to loaded entity instances, by identifier. Filled in through
+/// `RegisterLdtkEntity::register_ldtk_entity`.
+#[derive(Default)]
+pub struct LdtkEntityRegistry {
+    spawners: HashMap<String, Vec<LdtkEntitySpawner>>,
+}
+
+pub trait RegisterLdtkEntity {
+    /// Adds the component returned by `build` to every loaded entity instance with the given
+    /// identifier, next to its `LdtkEntity`.
+    fn register_ldtk_entity<T: Component>(
+        &mut self,
+        identifier: &str,
+        build: impl Fn(&LdtkEntity) -> T + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterLdtkEntity for AppBuilder {
+    fn register_ldtk_entity<T: Component>(
+        &mut self,
+        identifier: &str,
+        build: impl Fn(&LdtkEntity) -> T + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(LdtkEntityRegistry::default)
+            .spawners
+            .entry(identifier.to_string())
+            .or_default()
+            .push(Box::new(move |entity_commands, entity| {
+                entity_commands.insert(build(entity));
+            }));
+        self
+    }
+}
+
 pub struct LdtkLoader;
 
 impl AssetLoader for LdtkLoader {
@@ -34,15 +368,13 @@ impl AssetLoader for LdtkLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let project: ldtk_rust::Project = serde_json::from_slice(bytes)?;
-            let dependencies: Vec<(i64, AssetPath)> = project.defs.tilesets.iter().map(|tileset| {
+            let mut ldtk_map = LdtkMap::from_json(serde_json::from_slice(bytes)?)?;
+            let dependencies: Vec<(i64, AssetPath)> = ldtk_map.project.defs.tilesets.iter().map(|tileset| {
                 (tileset.uid, load_context.path().parent().unwrap().join(tileset.rel_path.clone()).into())
             }).collect();
+            ldtk_map.tilesets = dependencies.iter().map(|dep| (dep.0, load_context.get_handle(dep.1.clone()))).collect();
 
-            let loaded_asset = LoadedAsset::new(LdtkMap {
-                project,
-                tilesets: dependencies.iter().map(|dep| (dep.0, load_context.get_handle(dep.1.clone()))).collect()
-            });
+            let loaded_asset = LoadedAsset::new(ldtk_map);
             load_context.set_default_asset(loaded_asset.with_dependencies(dependencies.iter().map(|x| x.1.clone()).collect()));
             Ok(())
         })
@@ -60,15 +392,20 @@ pub fn process_loaded_tile_maps(
     maps: Res<Assets<LdtkMap>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    entity_registry: Res<LdtkEntityRegistry>,
     mut query: Query<(
         Entity,
         &Handle<LdtkMap>,
         &LdtkMapConfig,
         &mut Map,
+        &Transform,
     )>,
-    new_maps: Query<&Handle<LdtkMap>, Added<Handle<LdtkMap>>>,
+    mut level_spawned: EventWriter<LdtkLevelSpawned>,
+    reselected_maps: Query<Entity, Or<(Changed<Handle<LdtkMap>>, Changed<LdtkMapConfig>)>>,
     layer_query: Query<&Layer>,
     chunk_query: Query<&Chunk>,
+    entity_query: Query<(Entity, &LdtkEntity)>,
+    int_grid_query: Query<(Entity, &LdtkIntGrid)>,
 ) {
     let mut changed_maps = Vec::<Handle<LdtkMap>>::default();
     for event in map_events.iter() {
@@ -90,127 +427,346 @@ pub fn process_loaded_tile_maps(
         }
     }
 
-    // If we have new map entities add them to the changed_maps list.
-    for new_map_handle in new_maps.iter() {
-        changed_maps.push(new_map_handle.clone());
-    }
+    // New maps and maps with a newly selected level are processed on their own, so adding a
+    // level of a world doesn't reload the others.
+    let reselected_maps = reselected_maps.iter().collect::<HashSet<Entity>>();
 
-    for changed_map in changed_maps.iter() {
-        for (_, map_handle, map_config, mut map) in query.iter_mut() {
-            // only deal with currently changed map
-            if map_handle != changed_map {
-                continue;
-            }
-            if let Some(ldtk_map) = maps.get(map_handle) {
-                // Despawn all tiles/chunks/layers.
-                for (layer_id, layer_entity) in map.get_layers() {
-                    if let Ok(layer) = layer_query.get(layer_entity) {
-                        for x in 0..layer.get_layer_size_in_tiles().x {
-                            for y in 0..layer.get_layer_size_in_tiles().y {
-                                let tile_pos = UVec2::new(x, y);
-                                let chunk_pos = UVec2::new(
-                                    tile_pos.x / layer.settings.chunk_size.x,
-                                    tile_pos.y / layer.settings.chunk_size.y,
-                                );
-                                if let Some(chunk_entity) = layer.get_chunk(chunk_pos) {
-                                    if let Ok(chunk) = chunk_query.get(chunk_entity) {
-                                        let chunk_tile_pos = chunk.to_chunk_pos(tile_pos);
-                                        if let Some(tile) = chunk.get_tile_entity(chunk_tile_pos) {
-                                            commands.entity(tile).despawn_recursive();
-                                        }
-                                    }
+    for (map_entity, map_handle, map_config, mut map, map_transform) in query.iter_mut() {
+        // only deal with currently changed map
+        if !changed_maps.contains(map_handle) && !reselected_maps.contains(&map_entity) {
+            continue;
+        }
+        if let Some(ldtk_map) = maps.get(map_handle) {
+            let level_index = match map_config.selected_level.index(ldtk_map) {
+                Some(level_index) => level_index,
+                None => {
+                    log::warn!("No level {:?} in the LDtk project", map_config.selected_level);
+                    continue;
+                }
+            };
 
-                                    commands.entity(chunk_entity).despawn_recursive();
+            // Despawn all tiles/chunks/layers.
+            for (layer_id, layer_entity) in map.get_layers() {
+                if let Ok(layer) = layer_query.get(layer_entity) {
+                    for x in 0..layer.get_layer_size_in_tiles().x {
+                        for y in 0..layer.get_layer_size_in_tiles().y {
+                            let tile_pos = UVec2::new(x, y);
+                            let chunk_pos = UVec2::new(
+                                tile_pos.x / layer.settings.chunk_size.x,
+                                tile_pos.y / layer.settings.chunk_size.y,
+                            );
+                            if let Some(chunk_entity) = layer.get_chunk(chunk_pos) {
+                                if let Ok(chunk) = chunk_query.get(chunk_entity) {
+                                    let chunk_tile_pos = chunk.to_chunk_pos(tile_pos);
+                                    if let Some(tile) = chunk.get_tile_entity(chunk_tile_pos) {
+                                        commands.entity(tile).despawn_recursive();
+                                    }
                                 }
+
+                                commands.entity(chunk_entity).despawn_recursive();
                             }
                         }
                     }
-                    map.remove_layer(&mut commands, layer_id);
                 }
+                map.remove_layer(&mut commands, layer_id);
+            }
+            for (entity, ldtk_entity) in entity_query.iter() {
+                if ldtk_entity.map == map_entity {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            for (entity, int_grid) in int_grid_query.iter() {
+                if int_grid.map == map_entity {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
 
-                // Pull out tilesets.
-                let mut tilesets = HashMap::new();
-                ldtk_map.project.defs.tilesets.iter().for_each(|tileset| {
-                    tilesets.insert(tileset.uid, (ldtk_map.tilesets.get(&tileset.uid).unwrap().clone(), tileset.clone()));
-                });
+            // Pull out tilesets.
+            let mut tilesets = HashMap::new();
+            ldtk_map.project.defs.tilesets.iter().for_each(|tileset| {
+                tilesets.insert(tileset.uid, (ldtk_map.tilesets.get(&tileset.uid).unwrap().clone(), tileset.clone()));
+            });
 
-                let default_grid_size = ldtk_map.project.default_grid_size;
-                let level = &ldtk_map.project.levels[map_config.selected_level];
+            let default_grid_size = ldtk_map.project.default_grid_size;
+            let level = &ldtk_map.project.levels[level_index];
 
-                let map_tile_count_x = (level.px_wid / default_grid_size) as u32;
-                let map_tile_count_y = (level.px_hei / default_grid_size) as u32;
+            let map_tile_count_x = (level.px_wid / default_grid_size) as u32;
+            let map_tile_count_y = (level.px_hei / default_grid_size) as u32;
 
-                let map_size = UVec2::new(
-                    (map_tile_count_x as f32 / 32.0).ceil() as u32,
-                    (map_tile_count_y as f32 / 32.0).ceil() as u32,
-                );
+            let map_size = UVec2::new(
+                (map_tile_count_x as f32 / 32.0).ceil() as u32,
+                (map_tile_count_y as f32 / 32.0).ceil() as u32,
+            );
 
-                for (layer_id, layer) in level.layer_instances.as_ref().unwrap().iter().rev().enumerate() {
-                    let (texture, tileset) = if let Some(uid) = layer.tileset_def_uid {
-                        tilesets.get(&uid).unwrap().clone()
-                    } else {
-                        continue;
+            for (layer_id, layer_instance) in level.layer_instances.as_ref().unwrap().iter().rev().enumerate() {
+                for instance in layer_instance.entity_instances.iter() {
+                    let grid_size = layer_instance.grid_size as f32;
+                    let size = Vec2::new(instance.width as f32, instance.height as f32);
+                    let pivot = Vec2::new(instance.pivot[0] as f32, instance.pivot[1] as f32);
+                    let px = IVec2::new(instance.px[0] as i32, instance.px[1] as i32);
+                    let offset = Vec2::new(layer_instance.px_total_offset_x as f32, layer_instance.px_total_offset_y as f32);
+                    let center = px.as_f32() + offset + (Vec2::splat(0.5) - pivot) * size;
+                    // the same flip as the tiles below, which are moved up a row
+                    let position = Vec3::new(
+                        center.x,
+                        (map_tile_count_y + 1) as f32 * default_grid_size as f32
+                            - center.y
+                            - level.px_hei as f32,
+                        layer_id as f32,
+                    );
+                    let ldtk_entity = LdtkEntity {
+                        identifier: instance.identifier.clone(),
+                        grid: IVec2::new(instance.grid[0] as i32, instance.grid[1] as i32),
+                        px,
+                        size,
+                        pivot,
+                        grid_size,
+                        tile: instance.tile.as_ref().map(|tile| LdtkEntityTile {
+                            tileset_uid: tile.tileset_uid,
+                            src_rect: [tile.src_rect[0], tile.src_rect[1], tile.src_rect[2], tile.src_rect[3]],
+                        }),
+                        fields: instance
+                            .field_instances
+                            .iter()
+                            .map(|field| {
+                                (field.identifier.clone(), field.value.clone().unwrap_or(serde_json::Value::Null))
+                            })
+                            .collect(),
+                        map: map_entity,
                     };
+                    let mut entity_commands = commands.spawn();
+                    entity_commands.insert_bundle((
+                        Transform::from_translation(map_transform.mul_vec3(position)),
+                        GlobalTransform::identity(),
+                    ));
+                    if let Some(spawners) = entity_registry.spawners.get(&ldtk_entity.identifier) {
+                        for spawner in spawners.iter() {
+                            spawner(&mut entity_commands, &ldtk_entity);
+                        }
+                    }
+                    entity_commands.insert(ldtk_entity);
+                }
 
-                    let mut settings = LayerSettings::new(
-                        map_size,
-                        UVec2::new(32, 32),
-                        Vec2::new(tileset.tile_grid_size as f32, tileset.tile_grid_size as f32),
-                        Vec2::new(tileset.px_wid as f32, tileset.px_hei as f32)
-                    );
-                    settings.set_layer_id(layer_id as u16);
-
-                    let (mut layer_builder, layer_entity) = LayerBuilder::<TileBundle>::new(
-                        &mut commands,
-                        settings,
-                        map.id,
-                        layer_id as u16,
-                        None,
-                    );
+                if !layer_instance.int_grid_csv.is_empty() {
+                    let int_grid = commands
+                        .spawn()
+                        .insert(LdtkIntGrid {
+                            identifier: layer_instance.identifier.clone(),
+                            layer_id: layer_id as u16,
+                            size: UVec2::new(layer_instance.c_wid as u32, layer_instance.c_hei as u32),
+                            rows: map_tile_count_y,
+                            grid_size: layer_instance.grid_size as f32,
+                            values: layer_instance.int_grid_csv.clone(),
+                            map: map_entity,
+                        })
+                        .id();
+                    commands.entity(map_entity).push_children(&[int_grid]);
+                }
+
+                let (texture, tileset) = if let Some(uid) = layer_instance.override_tileset_uid.or(layer_instance.tileset_def_uid) {
+                    tilesets.get(&uid).unwrap().clone()
+                } else {
+                    continue;
+                };
 
-                    let tileset_width_in_tiles = (tileset.px_wid / default_grid_size) as u32;
+                let mut settings = LayerSettings::new(
+                    map_size,
+                    UVec2::new(32, 32),
+                    Vec2::new(tileset.tile_grid_size as f32, tileset.tile_grid_size as f32),
+                    Vec2::new(tileset.px_wid as f32, tileset.px_hei as f32)
+                );
+                settings.set_layer_id(layer_id as u16);
+                // culling would show the chunks of hidden layers again
+                settings.cull = layer_instance.visible;
+
+                let (mut layer_builder, layer_entity) = LayerBuilder::<TileBundle>::new(
+                    &mut commands,
+                    settings,
+                    map.id,
+                    layer_id as u16,
+                    None,
+                );
+
+                let tileset_width_in_tiles = (tileset.px_wid / default_grid_size) as u32;
 
-                    for tile in layer.grid_tiles.iter() {
-                        // dbg!(&tile.t);
-                        let tileset_x = (tile.src[0] / default_grid_size) as u32;
-                        let tileset_y = (tile.src[1] / default_grid_size) as u32;
+                // auto-layer tiles are sorted back to front, so later ones win
+                for tile in layer_instance.grid_tiles.iter().chain(layer_instance.auto_layer_tiles.iter()) {
+                    // dbg!(&tile.t);
+                    let tileset_x = (tile.src[0] / default_grid_size) as u32;
+                    let tileset_y = (tile.src[1] / default_grid_size) as u32;
 
-                        let mut pos = UVec2::new(
-                            (tile.px[0] / default_grid_size) as u32,
-                            (tile.px[1] / default_grid_size) as u32
-                        );
+                    let mut pos = UVec2::new(
+                        (tile.px[0] / default_grid_size) as u32,
+                        (tile.px[1] / default_grid_size) as u32
+                    );
 // dbg!(&map_tile_count_y, &pos.y);
-                        pos.y = map_tile_count_y - pos.y;// - 1;
-
-                        layer_builder.set_tile(
-                            pos,
-                            Tile {
-                                texture_index: tile.t as u16,// dbg!(tileset_y * tileset_width_in_tiles + tileset_x) as u16,
-                                ..Default::default()
-                            }.into()
-                        ).unwrap();
-                    }
+                    pos.y = map_tile_count_y - pos.y;// - 1;
 
-                    let material_handle = materials.add(ColorMaterial::texture(texture));
-                    let layer_bundle = layer_builder.build(&mut commands, &mut meshes, material_handle);
-                    let mut layer = layer_bundle.layer;
-                    let mut transform = Transform::from_xyz(0.0, -level.px_hei as f32, layer_bundle.transform.translation.z);
-                    layer.settings.layer_id = layer.settings.layer_id;
-                    transform.translation.z = layer.settings.layer_id as f32;
-                    map.add_layer(&mut commands, layer.settings.layer_id, layer_entity);
-                    commands
-                        .entity(layer_entity)
-                        .insert_bundle(LayerBundle {
-                            layer,
-                            transform,
-                            ..layer_bundle
+                    layer_builder.set_tile(
+                        pos,
+                        Tile {
+                            texture_index: tile.t as u16,// dbg!(tileset_y * tileset_width_in_tiles + tileset_x) as u16,
+                            color: Color::rgba(1.0, 1.0, 1.0, layer_instance.opacity as f32),
+                            ..Default::default()
+                        }.into()
+                    ).unwrap();
+                }
+
+                let material_handle = materials.add(ColorMaterial::texture(texture));
+                let layer_bundle = layer_builder.build(&mut commands, &mut meshes, material_handle);
+                let mut layer = layer_bundle.layer;
+                let offset = Vec2::new(layer_instance.px_total_offset_x as f32, -layer_instance.px_total_offset_y as f32);
+                let mut transform = Transform::from_xyz(offset.x, offset.y - level.px_hei as f32, layer_bundle.transform.translation.z);
+                layer.settings.layer_id = layer.settings.layer_id;
+                transform.translation.z = layer.settings.layer_id as f32;
+                if !layer_instance.visible {
+                    for chunk_entity in layer.chunks.iter().flatten() {
+                        commands.entity(*chunk_entity).insert(Visible {
+                            is_visible: false,
+                            is_transparent: true,
                         });
+                    }
                 }
+                let (level_min, level_max) = ldtk_map.level_bounds(level_index);
+                let ldtk_layer = LdtkLayer {
+                    identifier: layer_instance.identifier.clone(),
+                    layer_id: layer.settings.layer_id,
+                    opacity: layer_instance.opacity as f32,
+                    visible: layer_instance.visible,
+                    offset,
+                    parallax: ldtk_map.layer_parallax.get(&layer_instance.layer_def_uid).copied().unwrap_or(Vec2::ZERO),
+                    base_translation: transform.translation,
+                    parallax_anchor: map_transform.mul_vec3(((level_min + level_max) / 2.0).extend(0.0)).truncate(),
+                };
+                map.add_layer(&mut commands, layer.settings.layer_id, layer_entity);
+                commands
+                    .entity(layer_entity)
+                    .insert_bundle(LayerBundle {
+                        layer,
+                        transform,
+                        ..layer_bundle
+                    })
+                    .insert(ldtk_layer);
             }
+
+            level_spawned.send(LdtkLevelSpawned {
+                map: map_entity,
+                map_id: map.id,
+                level: level_index,
+                identifier: level.identifier.clone(),
+            });
+        }
+    }
+}
+
+/// Keeps `LdtkWorld::levels` to the levels near an `LdtkWorldFocus`.
+pub fn focus_ldtk_worlds(
+    maps: Res<Assets<LdtkMap>>,
+    focus_query: Query<(&GlobalTransform, &LdtkWorldFocus)>,
+    mut world_query: Query<(&Handle<LdtkMap>, &Transform, &mut LdtkWorld)>,
+) {
+    if focus_query.iter().next().is_none() {
+        return;
+    }
+    for (map_handle, world_transform, mut world) in world_query.iter_mut() {
+        let ldtk_map = match maps.get(map_handle) {
+            Some(ldtk_map) => ldtk_map,
+            None => continue,
+        };
+        let levels = (0..ldtk_map.project.levels.len())
+            .filter(|index| {
+                let offset = ldtk_map.level_map_offset(*index);
+                let (min, max) = ldtk_map.level_bounds(*index);
+                let min = world_transform.mul_vec3((offset + min).extend(0.0)).truncate();
+                let max = world_transform.mul_vec3((offset + max).extend(0.0)).truncate();
+                focus_query.iter().any(|(focus_transform, focus)| {
+                    let point = focus_transform.translation.truncate();
+                    let closest = point.max(min.min(max)).min(max.max(min));
+                    point.distance(closest) <= focus.load_distance
+                })
+            })
+            .collect::<HashSet<usize>>();
+        // only touch the world when the levels change, to keep change detection useful
+        if world.levels != levels {
+            world.levels = levels;
         }
     }
 }
 
+/// Spawns and despawns the level maps of every `LdtkWorld` to match `LdtkWorld::levels`.
+pub fn spawn_ldtk_world_levels(
+    mut commands: Commands,
+    maps: Res<Assets<LdtkMap>>,
+    mut world_query: Query<(Entity, &Handle<LdtkMap>, &Transform, &mut LdtkWorld)>,
+    entity_query: Query<(Entity, &LdtkEntity)>,
+    mut map_query: MapQuery,
+) {
+    for (world_entity, map_handle, world_transform, mut world) in world_query.iter_mut() {
+        let ldtk_map = match maps.get(map_handle) {
+            Some(ldtk_map) => ldtk_map,
+            None => continue,
+        };
+
+        let unloaded = world
+            .spawned
+            .iter()
+            .filter(|(index, _)| !world.levels.contains(index))
+            .map(|(index, level_entity)| (*index, *level_entity))
+            .collect::<Vec<(usize, Entity)>>();
+        for (index, level_entity) in unloaded {
+            world.spawned.remove(&index);
+            map_query.despawn(&mut commands, world.map_id(index));
+            for (entity, ldtk_entity) in entity_query.iter() {
+                if ldtk_entity.map == level_entity {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+
+        let loaded = world
+            .levels
+            .iter()
+            .filter(|index| **index < ldtk_map.project.levels.len() && !world.spawned.contains_key(index))
+            .copied()
+            .collect::<Vec<usize>>();
+        for index in loaded {
+            let level_entity = commands.spawn().id();
+            let offset = ldtk_map.level_map_offset(index);
+            commands.entity(level_entity).insert_bundle(LdtkMapBundle {
+                ldtk_map: map_handle.clone(),
+                map: Map::new(world.map_id(index), level_entity),
+                transform: world_transform.mul_transform(Transform::from_translation(offset.extend(0.0))),
+                ldtk_map_config: LdtkMapConfig { selected_level: LevelSelection::Index(index) },
+                ..Default::default()
+            }).insert(LdtkLevel {
+                identifier: ldtk_map.project.levels[index].identifier.clone(),
+                index,
+                world: world_entity,
+            });
+            world.spawned.insert(index, level_entity);
+        }
+    }
+}
+
+/// Moves layers with parallax factors along with the `LdtkParallaxCamera`, so they line up with
+/// the other layers while the camera is at the center of the level.
+pub fn scroll_ldtk_parallax_layers(
+    camera_query: Query<&GlobalTransform, With<LdtkParallaxCamera>>,
+    mut layer_query: Query<(&LdtkLayer, &mut Transform)>,
+) {
+    let camera = match camera_query.iter().next() {
+        Some(camera_transform) => camera_transform.translation.truncate(),
+        None => return,
+    };
+    for (ldtk_layer, mut transform) in layer_query.iter_mut() {
+        if ldtk_layer.parallax == Vec2::ZERO {
+            continue;
+        }
+        let scroll = (camera - ldtk_layer.parallax_anchor) * ldtk_layer.parallax;
+        transform.translation = ldtk_layer.base_translation + scroll.extend(0.0);
+    }
+}
+
 /// Adds the default systems and pipelines used by bevy_ecs_tilemap::ldtk.
 #[derive(Default)]
 pub struct LdtkPlugin;
@@ -220,6 +776,341 @@ impl Plugin for LdtkPlugin {
         app
             .add_asset::<LdtkMap>()
             .add_asset_loader(LdtkLoader)
-            .add_system(process_loaded_tile_maps.system());
+            .add_event::<LdtkLevelSpawned>()
+            .init_resource::<LdtkEntityRegistry>()
+            .add_system(process_loaded_tile_maps.system())
+            .add_system(focus_ldtk_worlds.system().before("spawn_ldtk_world_levels"))
+            .add_system(spawn_ldtk_world_levels.system().label("spawn_ldtk_world_levels"))
+            .add_system(scroll_ldtk_parallax_layers.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+
+    /// The example project: one 256x256 level with the empty `Tiles2` layer over `Tiles`.
+    fn project() -> serde_json::Value {
+        serde_json::from_str(include_str!("../assets/map.ldtk")).unwrap()
+    }
+
+    fn ldtk_map(project: serde_json::Value) -> LdtkMap {
+        let mut ldtk_map = LdtkMap::from_json(project).unwrap();
+        for tileset in ldtk_map.project.defs.tilesets.iter() {
+            ldtk_map.tilesets.insert(tileset.uid, Handle::default());
+        }
+        ldtk_map
+    }
+
+    /// An app with the LDtk plugin, but nothing to render with.
+    fn app() -> App {
+        let mut app = App::build();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<Texture>()
+            .add_asset::<ColorMaterial>()
+            .add_plugin(LdtkPlugin);
+        app.app
+    }
+
+    fn spawn_map(app: &mut App, ldtk_map: LdtkMap) -> Entity {
+        let handle = app.world.get_resource_mut::<Assets<LdtkMap>>().unwrap().add(ldtk_map);
+        let map_entity = app.world.spawn().id();
+        app.world.entity_mut(map_entity).insert_bundle(LdtkMapBundle {
+            ldtk_map: handle,
+            map: Map::new(0u16, map_entity),
+            ..Default::default()
+        });
+        map_entity
+    }
+
+    /// Whether the chunks of each layer of the spawned level are visible, by identifier.
+    fn chunk_visibility(app: &mut App) -> HashMap<String, Vec<bool>> {
+        let layers = app
+            .world
+            .query::<(&LdtkLayer, &Layer)>()
+            .iter(&app.world)
+            .map(|(ldtk_layer, layer)| (ldtk_layer.identifier.clone(), layer.chunks.iter().flatten().copied().collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        layers
+            .into_iter()
+            .map(|(identifier, chunks)| {
+                let visible = chunks.iter().map(|chunk| app.world.get::<Visible>(*chunk).unwrap().is_visible).collect();
+                (identifier, visible)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn visible_layers_have_visible_chunks() {
+        let mut app = app();
+        spawn_map(&mut app, ldtk_map(project()));
+        app.update();
+
+        let visibility = chunk_visibility(&mut app);
+        assert_eq!(visibility.len(), 2);
+        for (identifier, chunks) in visibility {
+            assert!(!chunks.is_empty(), "{} has no chunks", identifier);
+            assert!(chunks.iter().all(|visible| *visible), "{} is hidden", identifier);
+        }
+    }
+
+    #[test]
+    fn hidden_layers_have_invisible_chunks() {
+        let mut project = project();
+        // `Tiles`, the layer with the tiles
+        project["levels"][0]["layerInstances"][1]["visible"] = false.into();
+        let mut app = app();
+        spawn_map(&mut app, ldtk_map(project));
+        app.update();
+
+        let visibility = chunk_visibility(&mut app);
+        assert!(visibility["Tiles"].iter().all(|visible| !*visible));
+        assert!(visibility["Tiles2"].iter().all(|visible| *visible));
+        let ldtk_layers = app.world.query::<&LdtkLayer>().iter(&app.world).map(|layer| (layer.identifier.clone(), layer.visible)).collect::<HashMap<_, _>>();
+        assert!(!ldtk_layers["Tiles"]);
+        assert!(ldtk_layers["Tiles2"]);
+    }
+
+    /// Appends a copy of the first level, `world_x`/`world_y` pixels into the world and
+    /// `px_wid` by `px_hei` pixels in size.
+    fn add_level(project: &mut serde_json::Value, world_x: i64, world_y: i64, px_wid: i64, px_hei: i64) {
+        let levels = project["levels"].as_array_mut().unwrap();
+        let mut level = levels[0].clone();
+        level["uid"] = (levels.len() as i64 + 100).into();
+        level["identifier"] = format!("Level_{}", levels.len()).into();
+        level["worldX"] = world_x.into();
+        level["worldY"] = world_y.into();
+        level["pxWid"] = px_wid.into();
+        level["pxHei"] = px_hei.into();
+        levels.push(level);
+    }
+
+    #[test]
+    fn level_offset_follows_world_layout() {
+        let mut project = project();
+        add_level(&mut project, 256, 64, 128, 100);
+        add_level(&mut project, -300, -50, 64, 64);
+        let offsets = |layout: &str| {
+            let mut project = project.clone();
+            project["worldLayout"] = layout.into();
+            let ldtk_map = ldtk_map(project);
+            (0..3).map(|index| ldtk_map.level_offset(index)).collect::<Vec<_>>()
+        };
+
+        let free = vec![Vec2::new(0.0, 0.0), Vec2::new(256.0, -64.0), Vec2::new(-300.0, 50.0)];
+        assert_eq!(offsets("Free"), free);
+        assert_eq!(offsets("GridVania"), free);
+        assert_eq!(offsets("LinearHorizontal"), vec![Vec2::new(0.0, 0.0), Vec2::new(256.0, 0.0), Vec2::new(384.0, 0.0)]);
+        assert_eq!(offsets("LinearVertical"), vec![Vec2::new(0.0, 0.0), Vec2::new(0.0, -256.0), Vec2::new(0.0, -356.0)]);
+    }
+
+    #[test]
+    fn level_bounds_cover_whole_cells() {
+        let mut project = project();
+        add_level(&mut project, 0, 0, 250, 250);
+        let ldtk_map = ldtk_map(project);
+
+        assert_eq!(ldtk_map.level_bounds(0), (Vec2::new(0.0, -240.0), Vec2::new(256.0, 16.0)));
+        // the partial bottom row of cells reaches down to the bottom of the level
+        assert_eq!(ldtk_map.level_bounds(1), (Vec2::new(0.0, -250.0), Vec2::new(256.0, 6.0)));
+        assert_eq!(ldtk_map.level_map_offset(0), Vec2::new(0.0, -16.0));
+        assert_eq!(ldtk_map.level_map_offset(1), Vec2::new(0.0, -6.0));
+    }
+
+    #[test]
+    fn level_bounds_match_spawned_tiles() {
+        let mut app = app();
+        spawn_map(&mut app, ldtk_map(project()));
+        app.update();
+
+        let (layer_id, layer_y) = app
+            .world
+            .query::<(&LdtkLayer, &Transform)>()
+            .iter(&app.world)
+            .find(|(ldtk_layer, _)| ldtk_layer.identifier == "Tiles")
+            .map(|(ldtk_layer, transform)| (ldtk_layer.layer_id, transform.translation.y))
+            .unwrap();
+        let tiles = app
+            .world
+            .query::<(&UVec2, &TileParent)>()
+            .iter(&app.world)
+            .filter(|(_, parent)| parent.layer_id == layer_id)
+            .map(|(pos, _)| *pos)
+            .collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 256);
+        let bottom = tiles.iter().map(|pos| pos.y).min().unwrap() as f32 * 16.0 + layer_y;
+        let top = (tiles.iter().map(|pos| pos.y).max().unwrap() + 1) as f32 * 16.0 + layer_y;
+        let right = (tiles.iter().map(|pos| pos.x).max().unwrap() + 1) as f32 * 16.0;
+        assert_eq!(ldtk_map(project()).level_bounds(0), (Vec2::new(0.0, bottom), Vec2::new(right, top)));
+    }
+
+    /// The example project with two more levels, the later ones with instance ids.
+    fn selection_map(iids: bool) -> LdtkMap {
+        let mut project = project();
+        add_level(&mut project, 256, 0, 256, 256);
+        add_level(&mut project, 512, 0, 256, 256);
+        if iids {
+            project["levels"][1]["iid"] = "a2d1f3b0-1f60-11ec-b1a8-8f1b3c5e8e42".into();
+            project["levels"][2]["iid"] = "a2d1f3b1-1f60-11ec-b1a8-8f1b3c5e8e42".into();
+        }
+        ldtk_map(project)
+    }
+
+    #[test]
+    fn level_selection_by_index() {
+        let ldtk_map = selection_map(false);
+        assert_eq!(LevelSelection::Index(0).index(&ldtk_map), Some(0));
+        assert_eq!(LevelSelection::Index(2).index(&ldtk_map), Some(2));
+        assert_eq!(LevelSelection::Index(3).index(&ldtk_map), None);
+    }
+
+    #[test]
+    fn level_selection_by_identifier() {
+        let ldtk_map = selection_map(false);
+        assert_eq!(LevelSelection::Identifier("Level_0".to_string()).index(&ldtk_map), Some(0));
+        assert_eq!(LevelSelection::Identifier("Level_2".to_string()).index(&ldtk_map), Some(2));
+        assert_eq!(LevelSelection::Identifier("Level_3".to_string()).index(&ldtk_map), None);
+    }
+
+    #[test]
+    fn level_selection_by_uid() {
+        let ldtk_map = selection_map(false);
+        assert_eq!(LevelSelection::Uid(0).index(&ldtk_map), Some(0));
+        assert_eq!(LevelSelection::Uid(101).index(&ldtk_map), Some(1));
+        assert_eq!(LevelSelection::Uid(1).index(&ldtk_map), None);
+    }
+
+    #[test]
+    fn level_selection_by_iid() {
+        let ldtk_map = selection_map(true);
+        assert_eq!(ldtk_map.level_iids[0], None);
+        assert_eq!(LevelSelection::Iid("a2d1f3b1-1f60-11ec-b1a8-8f1b3c5e8e42".to_string()).index(&ldtk_map), Some(2));
+        assert_eq!(LevelSelection::Iid("a2d1f3b2-1f60-11ec-b1a8-8f1b3c5e8e42".to_string()).index(&ldtk_map), None);
+        // projects saved before LDtk 1.0 have no instance ids to select by
+        let ldtk_map = selection_map(false);
+        assert_eq!(LevelSelection::Iid("a2d1f3b1-1f60-11ec-b1a8-8f1b3c5e8e42".to_string()).index(&ldtk_map), None);
+    }
+
+    /// Spawns a world of the example level and a copy of it 1000 pixels to the right, with a
+    /// focus at `position` loading the levels within 100 pixels.
+    fn spawn_world(app: &mut App, position: Vec2) -> (Entity, Entity) {
+        let mut project = project();
+        add_level(&mut project, 1000, 0, 256, 256);
+        let handle = app.world.get_resource_mut::<Assets<LdtkMap>>().unwrap().add(ldtk_map(project));
+        let world_entity = app.world.spawn().insert_bundle(LdtkWorldBundle { ldtk_map: handle, ..Default::default() }).id();
+        let focus_entity = app
+            .world
+            .spawn()
+            .insert(GlobalTransform::from_translation(position.extend(0.0)))
+            .insert(LdtkWorldFocus { load_distance: 100.0 })
+            .id();
+        (world_entity, focus_entity)
+    }
+
+    fn spawned_levels(app: &mut App) -> Vec<usize> {
+        let mut levels = app.world.query::<&LdtkLevel>().iter(&app.world).map(|level| level.index).collect::<Vec<_>>();
+        levels.sort_unstable();
+        levels
+    }
+
+    #[test]
+    fn world_loads_levels_near_focus() {
+        let mut app = app();
+        let (world_entity, _) = spawn_world(&mut app, Vec2::new(128.0, -128.0));
+        app.update();
+
+        assert_eq!(spawned_levels(&mut app), vec![0]);
+        let world = app.world.get::<LdtkWorld>(world_entity).unwrap();
+        let level_entity = world.level_entity(0).unwrap();
+        assert_eq!(world.level_entity(1), None);
+        // the top left corner of the level is at its offset
+        let transform = app.world.get::<Transform>(level_entity).unwrap();
+        assert_eq!(transform.translation, Vec3::new(0.0, -16.0, 0.0));
+    }
+
+    #[test]
+    fn world_loads_levels_within_load_distance() {
+        let mut app = app();
+        // 99 pixels right of the first level, 744 left of the second
+        spawn_world(&mut app, Vec2::new(355.0, -128.0));
+        app.update();
+        assert_eq!(spawned_levels(&mut app), vec![0]);
+
+        // 99 pixels left of the second level
+        let mut app = self::app();
+        spawn_world(&mut app, Vec2::new(901.0, 10.0));
+        app.update();
+        assert_eq!(spawned_levels(&mut app), vec![1]);
+    }
+
+    #[test]
+    fn world_unloads_levels_away_from_focus() {
+        let mut app = app();
+        let (world_entity, focus_entity) = spawn_world(&mut app, Vec2::new(128.0, -128.0));
+        app.update();
+        app.update();
+        assert!(app.world.query::<&LdtkLayer>().iter(&app.world).count() > 0);
+
+        app.world.get_mut::<GlobalTransform>(focus_entity).unwrap().translation = Vec3::new(1128.0, -128.0, 0.0);
+        app.update();
+        app.update();
+
+        assert_eq!(spawned_levels(&mut app), vec![1]);
+        let world = app.world.get::<LdtkWorld>(world_entity).unwrap();
+        assert_eq!(world.level_entity(0), None);
+        assert!(world.level_entity(1).is_some());
+        let maps = app.world.query::<&Map>().iter(&app.world).map(|map| map.id).collect::<Vec<_>>();
+        assert_eq!(maps, vec![1]);
+    }
+
+    fn int_grid(px_wid: u32, px_hei: u32, grid_size: u32) -> LdtkIntGrid {
+        // LDtk rounds cell counts up, the loader rounds tile rows down
+        let size = UVec2::new((px_wid + grid_size - 1) / grid_size, (px_hei + grid_size - 1) / grid_size);
+        LdtkIntGrid {
+            identifier: "Collisions".to_string(),
+            layer_id: 0,
+            size,
+            rows: px_hei / grid_size,
+            grid_size: grid_size as f32,
+            values: (0..(size.x * size.y) as i64).map(|i| i + 1).collect(),
+            map: Entity::new(0),
+        }
+    }
+
+    #[test]
+    fn get_matches_tile_flip() {
+        let grid = int_grid(36, 72, 18);
+        assert_eq!(grid.size, UVec2::new(2, 4));
+        // tile row 4 is the top row of cells
+        assert_eq!(grid.get(UVec2::new(0, 4)), grid.get_cell(IVec2::new(0, 0)));
+        assert_eq!(grid.get(UVec2::new(1, 1)), grid.get_cell(IVec2::new(1, 3)));
+        assert_eq!(grid.get(UVec2::new(0, 0)), 0);
+    }
+
+    #[test]
+    fn get_flips_by_tile_rows_when_height_is_not_a_multiple_of_grid() {
+        let grid = int_grid(36, 706, 18);
+        assert_eq!(grid.size.y, 40);
+        assert_eq!(grid.rows, 39);
+        // the top tile row is the top row of cells, not the one below it
+        assert_eq!(grid.get(UVec2::new(0, 39)), 1);
+        assert_eq!(grid.get(UVec2::new(1, 39)), 2);
+        // and the bottom tile row the last, partial row of cells
+        assert_eq!(grid.get(UVec2::new(1, 0)), 80);
+        for row in 0..=39 {
+            assert_eq!(grid.get(UVec2::new(1, row)), 80 - 2 * row as i64, "tile row {}", row);
+        }
+    }
+
+    #[test]
+    fn get_cell_outside_grid_is_empty() {
+        let grid = int_grid(36, 706, 18);
+        assert_eq!(grid.get_cell(IVec2::new(-1, 0)), 0);
+        assert_eq!(grid.get_cell(IVec2::new(2, 0)), 0);
+        assert_eq!(grid.get_cell(IVec2::new(0, 40)), 0);
+        assert_eq!(grid.get(UVec2::new(0, 40)), 0);
     }
 }
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{
+        AssetLoader, BoxedFuture, LoadContext, LoadedAsset,
+    },
+    prelude::*,
+    reflect::TypeUuid,
+};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    controller::PlayerController, state::GameState, Player,
+    PlayerDied, PlayerSprites,
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize,
+)]
+pub enum AnimationState {
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    Land,
+    Hurt,
+}
+
+/// A run of frames in the sprite sheet.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct AnimationClip {
+    /// Atlas index of the first frame.
+    pub first: u32,
+    /// Atlas index of the last frame, may be lower than
+    /// `first` to play backwards.
+    pub last: u32,
+    /// Seconds each frame is shown for.
+    pub frame_time: f32,
+    /// Clips that don't loop hold their last frame and
+    /// finish.
+    pub looping: bool,
+}
+
+impl AnimationClip {
+    fn len(&self) -> u32 {
+        if self.first <= self.last {
+            self.last - self.first + 1
+        } else {
+            self.first - self.last + 1
+        }
+    }
+
+    fn frame(&self, step: u32) -> u32 {
+        if self.first <= self.last {
+            self.first + step
+        } else {
+            self.first - step
+        }
+    }
+}
+
+/// The corgi's animation clips, loaded from `*.anim.ron`
+/// files next to the sprite sheet.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "c2f9e4d1-7a3b-4e58-8d16-0b9a4f3e2c71"]
+pub struct PlayerAnimations {
+    pub clips: HashMap<AnimationState, AnimationClip>,
+    /// Horizontal speed above which the corgi is running.
+    pub run_threshold: f32,
+}
+
+#[derive(Default)]
+pub struct PlayerAnimationsLoader;
+
+impl AssetLoader for PlayerAnimationsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let animations: PlayerAnimations =
+                ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(
+                LoadedAsset::new(animations),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["anim.ron"];
+        EXTENSIONS
+    }
+}
+
+/// Where the corgi is in its animation.
+#[derive(Debug)]
+pub struct PlayerAnimation {
+    pub state: AnimationState,
+    step: u32,
+    elapsed: f32,
+    finished: bool,
+}
+
+impl Default for PlayerAnimation {
+    fn default() -> Self {
+        Self {
+            state: AnimationState::Idle,
+            step: 0,
+            elapsed: 0.0,
+            finished: false,
+        }
+    }
+}
+
+impl PlayerAnimation {
+    /// Picks the state from the controller, letting landing
+    /// and getting hurt play out first.
+    fn next_state(
+        &self,
+        controller: &PlayerController,
+        velocity: &RigidBodyVelocity,
+        run_threshold: f32,
+    ) -> AnimationState {
+        use AnimationState::*;
+
+        if self.state == Hurt && !self.finished {
+            return Hurt;
+        }
+        if !controller.grounded {
+            return if velocity.linvel.y > 0.0 {
+                Jump
+            } else {
+                Fall
+            };
+        }
+        match self.state {
+            Jump | Fall => return Land,
+            Land if !self.finished => return Land,
+            _ => {}
+        }
+        if velocity.linvel.x.abs() > run_threshold {
+            Run
+        } else {
+            Idle
+        }
+    }
+
+    /// Moves through the clip's frames, looping or holding
+    /// the last one.
+    fn advance(
+        &mut self,
+        clip: &AnimationClip,
+        delta: f32,
+    ) {
+        self.elapsed += delta;
+        while !self.finished
+            && self.elapsed >= clip.frame_time
+        {
+            self.elapsed -= clip.frame_time;
+            self.step += 1;
+            if self.step >= clip.len() {
+                if clip.looping {
+                    self.step = 0;
+                } else {
+                    self.step = clip.len() - 1;
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+fn animate_player(
+    time: Res<Time>,
+    player_sprites: Res<PlayerSprites>,
+    animations: Res<Assets<PlayerAnimations>>,
+    mut player_died: EventReader<PlayerDied>,
+    mut players: Query<
+        (
+            &PlayerController,
+            &RigidBodyVelocity,
+            &mut PlayerAnimation,
+            &mut TextureAtlasSprite,
+        ),
+        With<Player>,
+    >,
+) {
+    let animations =
+        match animations.get(&player_sprites.animations) {
+            Some(animations) => animations,
+            None => return,
+        };
+    let died = player_died.iter().next().is_some();
+
+    for (controller, velocity, mut animation, mut sprite) in
+        players.iter_mut()
+    {
+        let state = if died {
+            AnimationState::Hurt
+        } else {
+            animation.next_state(
+                controller,
+                velocity,
+                animations.run_threshold,
+            )
+        };
+        let clip = match animations.clips.get(&state) {
+            Some(clip) => clip,
+            None => continue,
+        };
+
+        if state != animation.state || died {
+            *animation = PlayerAnimation {
+                state,
+                ..Default::default()
+            };
+        } else {
+            animation.advance(clip, time.delta_seconds());
+        }
+        sprite.index = clip.frame(animation.step);
+    }
+}
+
+pub struct PlayerAnimationPlugin;
+
+impl Plugin for PlayerAnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<PlayerAnimations>()
+            .init_asset_loader::<PlayerAnimationsLoader>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        animate_player
+                            .system()
+                            .after("detect_ground"),
+                    ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rapier2d::na::Vector2;
+
+    use super::{AnimationState::*, *};
+
+    #[test]
+    fn transitions() {
+        // state, finished, grounded, velocity, next state
+        #[rustfmt::skip]
+        let cases = [
+            (Idle, false, true, (0.0, 0.0), Idle),
+            (Idle, false, true, (3.0, 0.0), Run),
+            (Run, false, true, (-3.0, 0.0), Run),
+            (Run, false, true, (0.5, 0.0), Idle),
+            (Run, false, false, (3.0, 5.0), Jump),
+            (Jump, false, false, (0.0, -1.0), Fall),
+            (Idle, false, false, (0.0, -1.0), Fall),
+            (Fall, false, true, (3.0, 0.0), Land),
+            (Jump, false, true, (0.0, 0.0), Land),
+            (Land, false, true, (3.0, 0.0), Land),
+            (Land, true, true, (3.0, 0.0), Run),
+            (Land, false, false, (0.0, 5.0), Jump),
+            (Hurt, false, false, (0.0, -1.0), Hurt),
+            (Hurt, true, true, (0.0, 0.0), Idle),
+        ];
+        for (state, finished, grounded, (x, y), expected) in
+            cases.iter().copied()
+        {
+            let animation = PlayerAnimation {
+                state,
+                finished,
+                ..Default::default()
+            };
+            let mut controller =
+                PlayerController::default();
+            controller.grounded = grounded;
+            let velocity = RigidBodyVelocity {
+                linvel: Vector2::new(x, y),
+                angvel: 0.0,
+            };
+            assert_eq!(
+                animation.next_state(&controller, &velocity, 1.0),
+                expected,
+                "{:?} finished: {} grounded: {} velocity: {:?}",
+                state,
+                finished,
+                grounded,
+                (x, y),
+            );
+        }
+    }
+
+    #[test]
+    fn looping_clips_wrap_and_others_hold_the_last_frame() {
+        let clip = |looping| AnimationClip {
+            first: 7,
+            last: 5,
+            frame_time: 0.25,
+            looping,
+        };
+        let frames = |clip: AnimationClip| {
+            let mut animation = PlayerAnimation::default();
+            (0..6)
+                .map(|_| {
+                    animation.advance(&clip, 0.25);
+                    clip.frame(animation.step)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(frames(clip(true)), [6, 5, 7, 6, 5, 7]);
+        assert_eq!(frames(clip(false)), [6, 5, 5, 5, 5, 5]);
+
+        // a long frame skips ahead instead of slowing down
+        let mut animation = PlayerAnimation::default();
+        animation.advance(&clip(true), 0.6);
+        assert_eq!(animation.step, 2);
+    }
+}
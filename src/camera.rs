@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    controller::PlayerControllerConfig, state::GameState,
+    MainCamera, Player, PlayerRespawned,
+};
+
+/// Tuning for how the camera follows the corgi, in world
+/// pixels.
+#[derive(Debug, Clone)]
+pub struct CameraConfig {
+    /// Half size of the box around the focus point the corgi
+    /// can move in without moving the camera.
+    pub dead_zone: Vec2,
+    /// How far ahead of the corgi the camera looks when it
+    /// is running at full speed.
+    pub look_ahead: f32,
+    /// How quickly the camera catches up with its target,
+    /// higher is snappier.
+    pub smoothing: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: Vec2::new(32.0, 48.0),
+            look_ahead: 64.0,
+            smoothing: 5.0,
+        }
+    }
+}
+
+/// Follow state of the main camera.
+#[derive(Debug, Default)]
+pub struct CameraFollow {
+    /// The point the dead zone is centered on.
+    focus: Vec2,
+    look_ahead: f32,
+    /// Jump straight to the target on the next update
+    /// instead of easing towards it.
+    snap: bool,
+}
+
+/// The world space rectangle covered by a level map's
+/// tiles.
+fn level_bounds(
+    ldtk_map: &LdtkMap,
+    config: &LdtkMapConfig,
+    map_transform: &Transform,
+) -> Option<(Vec2, Vec2)> {
+    let index = config.selected_level.index(ldtk_map)?;
+    let (min, max) = ldtk_map.level_bounds(index);
+    let min = map_transform.mul_vec3(min.extend(0.0));
+    let max = map_transform.mul_vec3(max.extend(0.0));
+    Some((min.min(max).truncate(), min.max(max).truncate()))
+}
+
+fn snap_camera(mut cameras: Query<&mut CameraFollow>) {
+    for mut follow in cameras.iter_mut() {
+        follow.snap = true;
+    }
+}
+
+fn snap_camera_on_respawn(
+    mut player_respawned: EventReader<PlayerRespawned>,
+    cameras: Query<&mut CameraFollow>,
+) {
+    if player_respawned.iter().next().is_some() {
+        snap_camera(cameras);
+    }
+}
+
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+fn follow_player(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    config: Res<CameraConfig>,
+    controller_config: Res<PlayerControllerConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    ldtk_maps: Res<Assets<LdtkMap>>,
+    maps: Query<
+        (&Handle<LdtkMap>, &LdtkMapConfig, &Transform),
+        Without<MainCamera>,
+    >,
+    player: Query<
+        (&RigidBodyPosition, &RigidBodyVelocity),
+        With<Player>,
+    >,
+    mut cameras: Query<
+        (&mut Transform, &mut CameraFollow),
+        With<MainCamera>,
+    >,
+) {
+    let (position, velocity) = match player.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let translation = position.position.translation;
+    let player_position =
+        Vec2::new(translation.x, translation.y)
+            * rapier_config.scale;
+
+    for (mut transform, mut follow) in cameras.iter_mut() {
+        // move the focus just enough to keep the corgi
+        // inside the dead zone
+        let offset = player_position - follow.focus;
+        let outside = offset.abs() - config.dead_zone;
+        if follow.snap {
+            follow.focus = player_position;
+        } else {
+            if outside.x > 0.0 {
+                follow.focus.x +=
+                    outside.x * offset.x.signum();
+            }
+            if outside.y > 0.0 {
+                follow.focus.y +=
+                    outside.y * offset.y.signum();
+            }
+        }
+
+        let blend = if follow.snap {
+            1.0
+        } else {
+            1.0 - (-config.smoothing * time.delta_seconds())
+                .exp()
+        };
+        let look_ahead = config.look_ahead
+            * (velocity.linvel.x
+                / controller_config.max_run_speed)
+                .clamp(-1.0, 1.0);
+        follow.look_ahead +=
+            (look_ahead - follow.look_ahead) * blend;
+        let target = follow.focus
+            + Vec2::new(follow.look_ahead, 0.0);
+
+        let current = transform.translation.truncate();
+        let mut position =
+            current + (target - current) * blend;
+
+        if let Some((min, max)) = maps
+            .iter()
+            .filter_map(
+                |(handle, map_config, map_transform)| {
+                    level_bounds(
+                        ldtk_maps.get(handle)?,
+                        map_config,
+                        map_transform,
+                    )
+                },
+            )
+            // every level the world has spawned
+            .reduce(|(min, max), (level_min, level_max)| {
+                (min.min(level_min), max.max(level_max))
+            })
+        {
+            let half_view = windows.get_primary().map_or(
+                Vec2::ZERO,
+                |window| {
+                    Vec2::new(
+                        window.width(),
+                        window.height(),
+                    ) / 2.0
+                },
+            ) * transform.scale.truncate();
+            position = clamp_to_bounds(
+                position, half_view, min, max,
+            );
+        }
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        follow.snap = false;
+    }
+}
+
+/// Keeps a view of `half_view` around `position` inside
+/// `min`..`max`, centering it on axes where the bounds are
+/// smaller than the view.
+fn clamp_to_bounds(
+    position: Vec2,
+    half_view: Vec2,
+    min: Vec2,
+    max: Vec2,
+) -> Vec2 {
+    let clamp_axis = |position: f32,
+                      half_view: f32,
+                      min: f32,
+                      max: f32| {
+        if max - min <= half_view * 2.0 {
+            (min + max) / 2.0
+        } else {
+            position.clamp(min + half_view, max - half_view)
+        }
+    };
+    Vec2::new(
+        clamp_axis(position.x, half_view.x, min.x, max.x),
+        clamp_axis(position.y, half_view.y, min.y, max.y),
+    )
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CameraConfig>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(snap_camera.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        snap_camera_on_respawn
+                            .system()
+                            .label("snap_camera"),
+                    )
+                    .with_system(
+                        follow_player
+                            .system()
+                            .label("follow_player")
+                            .after("snap_camera"),
+                    ),
+            );
+    }
+}
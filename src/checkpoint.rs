@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    state::GameState,
+    tile_behaviour::{TileBehaviourTable, TileBehaviours},
+    Player, PlayerRespawned,
+};
+
+/// Identifier of the LDtk entity marking where the corgi
+/// starts a level.
+const PLAYER_START_IDENTIFIER: &str = "PlayerStart";
+
+/// Marks checkpoint tiles.
+pub struct Checkpoint;
+
+/// Where the corgi respawns after dying, in physics units.
+/// Moved by touching a checkpoint and reset at the start of
+/// every level.
+#[derive(Debug, Clone, Copy)]
+pub struct RespawnPoint(pub Vec2);
+
+impl Default for RespawnPoint {
+    fn default() -> Self {
+        // the `PlayerStart` of the shipped levels, for levels
+        // without one
+        Self(Vec2::new(-16.72, -4.04))
+    }
+}
+
+/// Where the corgi starts the level, spawned from the
+/// level's `PlayerStart` entity.
+pub struct PlayerStart;
+
+/// Moves the respawn point to the level's start once its
+/// entities are spawned, and the corgi along with it.
+#[allow(clippy::type_complexity)]
+fn move_to_player_start(
+    rapier_config: Res<RapierConfiguration>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut player_respawned: EventWriter<PlayerRespawned>,
+    starts: Query<&Transform, Added<PlayerStart>>,
+    mut player: Query<
+        (&mut RigidBodyPosition, &mut RigidBodyVelocity),
+        With<Player>,
+    >,
+) {
+    let transform = match starts.iter().next() {
+        Some(transform) => transform,
+        None => return,
+    };
+    respawn_point.0 = transform.translation.truncate()
+        / rapier_config.scale;
+    for (mut position, mut velocity) in player.iter_mut() {
+        *position = respawn_point.0.into();
+        *velocity = RigidBodyVelocity::default();
+        player_respawned.send(PlayerRespawned);
+    }
+}
+
+fn reset_respawn_point(
+    mut respawn_point: ResMut<RespawnPoint>,
+) {
+    *respawn_point = RespawnPoint::default();
+}
+
+/// Moves the respawn point to checkpoints the corgi touches
+/// and swaps them to their activated texture.
+#[allow(clippy::type_complexity)]
+fn activate_checkpoints(
+    narrow_phase: Res<NarrowPhase>,
+    player: Query<Entity, With<Player>>,
+    mut checkpoints: Query<
+        (Entity, &mut Tile, &UVec2, &ColliderPosition),
+        With<Checkpoint>,
+    >,
+    tile_behaviour_table: Res<TileBehaviourTable>,
+    tile_behaviours: Res<Assets<TileBehaviours>>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut map_query: MapQuery,
+) {
+    let player = match player.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let tile_behaviours = match tile_behaviours
+        .get(&tile_behaviour_table.0)
+    {
+        Some(tile_behaviours) => tile_behaviours,
+        None => return,
+    };
+    for (entity, mut tile, tile_pos, position) in
+        checkpoints.iter_mut()
+    {
+        if narrow_phase.intersection_pair(
+            player.handle(),
+            entity.handle(),
+        ) != Some(true)
+        {
+            continue;
+        }
+        let translation = position.0.translation;
+        let checkpoint =
+            Vec2::new(translation.x, translation.y);
+        if respawn_point.0 == checkpoint {
+            continue;
+        }
+        respawn_point.0 = checkpoint;
+
+        if let Some(activated) = tile_behaviours
+            .get(tile.texture_index)
+            .and_then(|definition| definition.activated)
+        {
+            tile.texture_index = activated;
+            map_query.notify_chunk_for_tile(
+                *tile_pos, 0u16, 0u16,
+            );
+        }
+    }
+}
+
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<RespawnPoint>()
+            .register_ldtk_entity(
+                PLAYER_START_IDENTIFIER,
+                |_| PlayerStart,
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(
+                        reset_respawn_point
+                            .system()
+                            .before("setup_level"),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        activate_checkpoints.system(),
+                    )
+                    .with_system(
+                        move_to_player_start.system(),
+                    ),
+            );
+    }
+}
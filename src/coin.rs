@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    prelude::*,
+};
+
+use crate::{
+    ldtk_entities::EntitySprites, score::CoinCollected,
+    state::GameState, Player,
+};
+
+/// Identifier of the LDtk entity coins are spawned from.
+const COIN_IDENTIFIER: &str = "Coin";
+
+/// Half the width and height of a coin's collider, in
+/// pixels.
+const COIN_HALF_EXTENT: f32 = 6.25;
+
+/// A coin the corgi can pick up.
+pub struct Coin;
+
+/// Gives the coins loaded from the level a sensor collider
+/// and the sprite LDtk shows them with.
+#[allow(clippy::type_complexity)]
+fn setup_coins(
+    mut commands: Commands,
+    rapier_config: Res<RapierConfiguration>,
+    ldtk_maps: Res<Assets<LdtkMap>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut sprites: Local<EntitySprites>,
+    maps: Query<&Handle<LdtkMap>>,
+    coins: Query<
+        (Entity, &LdtkEntity, &Transform),
+        Added<Coin>,
+    >,
+) {
+    for (entity, ldtk_entity, transform) in coins.iter() {
+        let (texture_atlas, index) = match sprites.tile(
+            ldtk_entity,
+            &maps,
+            &ldtk_maps,
+            &mut texture_atlases,
+        ) {
+            Some(tile) => tile,
+            None => {
+                warn!(
+                    "coin at {:?} has no tile, skipping",
+                    ldtk_entity.px
+                );
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        let world = transform.translation.truncate();
+        let position = world / rapier_config.scale;
+        let half_extent =
+            COIN_HALF_EXTENT / rapier_config.scale;
+
+        commands
+            .entity(entity)
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(
+                    half_extent,
+                    half_extent,
+                ),
+                collider_type: ColliderType::Sensor,
+                position: ColliderPosition(Isometry2::new(
+                    Vector2::new(position.x, position.y),
+                    0.0,
+                )),
+                ..Default::default()
+            })
+            .insert_bundle(SpriteSheetBundle {
+                texture_atlas,
+                sprite: TextureAtlasSprite::new(index),
+                transform: Transform::from_translation(
+                    world.extend(1.0),
+                ),
+                ..Default::default()
+            });
+    }
+}
+
+/// Picks up the coins the corgi touches.
+fn pick_up_coins(
+    mut commands: Commands,
+    narrow_phase: Res<NarrowPhase>,
+    player: Query<Entity, With<Player>>,
+    coins: Query<(Entity, &Transform), With<Coin>>,
+    mut coin_collected: EventWriter<CoinCollected>,
+) {
+    let player = match player.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    for (coin, transform) in coins.iter() {
+        if narrow_phase.intersection_pair(
+            player.handle(),
+            coin.handle(),
+        ) != Some(true)
+        {
+            continue;
+        }
+        coin_collected.send(CoinCollected {
+            position: transform.translation.truncate(),
+        });
+        commands.entity(coin).despawn_recursive();
+    }
+}
+
+pub struct CoinPlugin;
+
+impl Plugin for CoinPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.register_ldtk_entity(COIN_IDENTIFIER, |_| Coin)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(setup_coins.system())
+                    .with_system(pick_up_coins.system()),
+            );
+    }
+}
@@ -0,0 +1,720 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    prelude::*,
+};
+
+use crate::{
+    checkpoint::Checkpoint,
+    coords::TileCoords,
+    one_way::OneWayPlatform,
+    platforms::CrumblingTile,
+    spring::Spring,
+    tile_behaviour::{
+        TileBehaviour, TileBehaviourTable, TileBehaviours,
+        TileCollider, TileDefinition,
+    },
+    Spike,
+};
+
+/// Identifier of the optional LDtk IntGrid layer that holds
+/// a level's solid ground, in place of the tile textures.
+const COLLISION_GRID_IDENTIFIER: &str = "Collision";
+
+/// Marks tiles that already have their own collider.
+pub struct ProcessedTile;
+
+/// Marks the merged collider entities spawned for a chunk.
+pub struct ChunkCollider;
+
+/// The merged colliders for a chunk, kept so that they can be
+/// regenerated when the chunk's tiles change.
+pub struct ChunkColliders {
+    /// Behaviour of every merged tile in the chunk, row major.
+    tiles: Vec<Option<TileBehaviour>>,
+    colliders: Vec<Entity>,
+}
+
+/// A rectangle of tiles, in tile coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub min: UVec2,
+    pub size: UVec2,
+}
+
+/// Greedily merges equal cells of a row major grid into
+/// rectangles, extending runs horizontally first and then
+/// growing them upwards while the whole row matches.
+pub fn merge_tiles<T: Copy + PartialEq>(
+    size: UVec2,
+    cells: &[Option<T>],
+) -> Vec<(T, TileRect)> {
+    let index = |x: u32, y: u32| (y * size.x + x) as usize;
+    let mut merged = vec![false; cells.len()];
+    let mut rects = vec![];
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let kind = match cells[index(x, y)] {
+                Some(kind) if !merged[index(x, y)] => kind,
+                _ => continue,
+            };
+            let matches = |x: u32, y: u32| {
+                !merged[index(x, y)]
+                    && cells[index(x, y)] == Some(kind)
+            };
+
+            let mut width = 1;
+            while x + width < size.x
+                && matches(x + width, y)
+            {
+                width += 1;
+            }
+            let mut height = 1;
+            while y + height < size.y
+                && (x..x + width)
+                    .all(|x| matches(x, y + height))
+            {
+                height += 1;
+            }
+
+            for y in y..y + height {
+                for x in x..x + width {
+                    merged[index(x, y)] = true;
+                }
+            }
+            rects.push((
+                kind,
+                TileRect {
+                    min: UVec2::new(x, y),
+                    size: UVec2::new(width, height),
+                },
+            ));
+        }
+    }
+    rects
+}
+
+fn is_merged(behaviour: TileBehaviour) -> bool {
+    matches!(
+        behaviour,
+        TileBehaviour::Solid | TileBehaviour::OneWay
+    )
+}
+
+/// Solid and one-way tiles without a collider of their own
+/// are merged with their neighbours.
+fn is_merged_tile(definition: &TileDefinition) -> bool {
+    definition.collider.is_none()
+        && is_merged(definition.behaviour)
+}
+
+/// The merged collider for a value of the collision grid:
+/// `1` is solid ground and `2` a one-way platform.
+fn collision_grid_behaviour(
+    value: i64,
+) -> Option<TileBehaviour> {
+    match value {
+        1 => Some(TileBehaviour::Solid),
+        2 => Some(TileBehaviour::OneWay),
+        _ => None,
+    }
+}
+
+/// The merged tiles of a chunk taken from the collision
+/// grid, row major like `merge_tiles` expects.
+fn collision_grid_tiles(
+    collision_grid: &LdtkIntGrid,
+    chunk_origin: UVec2,
+    size: UVec2,
+) -> Vec<Option<TileBehaviour>> {
+    (0..size.y)
+        .flat_map(|y| {
+            (0..size.x).map(move |x| UVec2::new(x, y))
+        })
+        .map(|tile_pos| {
+            collision_grid_behaviour(
+                collision_grid.get(chunk_origin + tile_pos),
+            )
+        })
+        .collect()
+}
+
+/// The `Collision` IntGrid of the map a layer belongs to, if
+/// the level has one.
+pub fn find_collision_grid<'a>(
+    layer_entity: Entity,
+    transforms: &Query<(&Transform, Option<&Parent>)>,
+    int_grids: &'a Query<&LdtkIntGrid>,
+) -> Option<&'a LdtkIntGrid> {
+    let map_entity =
+        transforms.get(layer_entity).ok()?.1?.0;
+    int_grids.iter().find(|int_grid| {
+        int_grid.map == map_entity
+            && int_grid.identifier
+                == COLLISION_GRID_IDENTIFIER
+    })
+}
+
+/// What a tile of the main level layer collides as, the
+/// way `setup_colliders` builds its colliders: next to a
+/// collision grid, solid ground and one-way platforms come
+/// from the grid and merged tiles are only decoration.
+pub fn collision_behaviour(
+    definition: Option<&TileDefinition>,
+    collision_grid: Option<&LdtkIntGrid>,
+    tile_pos: UVec2,
+) -> Option<TileBehaviour> {
+    let collision_grid = match collision_grid {
+        Some(collision_grid) => collision_grid,
+        None => {
+            return definition
+                .map(|definition| definition.behaviour)
+        }
+    };
+    collision_grid_behaviour(collision_grid.get(tile_pos))
+        .or_else(|| {
+            definition
+                .filter(|definition| {
+                    !is_merged_tile(definition)
+                })
+                .map(|definition| definition.behaviour)
+        })
+}
+
+/// Crumbling tiles are solid, but keep colliders of their
+/// own so they can fall on their own.
+fn is_solid(behaviour: TileBehaviour) -> bool {
+    is_merged(behaviour)
+        || behaviour == TileBehaviour::Crumbling
+}
+
+/// Builds colliders for the main level layer.
+///
+/// Full tile solid and one-way colliders are merged per
+/// chunk into as few cuboids as possible, so the corgi
+/// doesn't snag on the seams between tiles. The merged
+/// colliders are rebuilt whenever the chunk changes, which
+/// is the case after `MapQuery::despawn_tile`, `set_tile` or
+/// `notify_chunk_for_tile`. Everything else gets a collider
+/// on the tile entity itself.
+///
+/// Levels with a `Collision` IntGrid layer take the merged
+/// colliders from the grid instead, and solid and one-way
+/// tiles are only decoration.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+pub fn setup_colliders(
+    mut commands: Commands,
+    mut chunks: Query<
+        (Entity, &Chunk, Option<&mut ChunkColliders>),
+        Or<(Changed<Chunk>, Without<ChunkColliders>)>,
+    >,
+    layers: Query<&Layer>,
+    transforms: Query<(&Transform, Option<&Parent>)>,
+    tiles: Query<(&Tile, &UVec2, Option<&ProcessedTile>)>,
+    int_grids: Query<&LdtkIntGrid>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    tile_behaviour_table: Res<TileBehaviourTable>,
+    tile_behaviours: Res<Assets<TileBehaviours>>,
+    rapier_config: Res<RapierConfiguration>,
+) {
+    // wait for the behaviour table before processing any
+    // chunks, otherwise they'd be marked as processed
+    // without colliders.
+    let tile_behaviours = match tile_behaviours
+        .get(&tile_behaviour_table.0)
+    {
+        Some(tile_behaviours) => tile_behaviours,
+        None => return,
+    };
+
+    for (entity, chunk, chunk_colliders) in
+        chunks.iter_mut().filter(|(_, chunk, _)| {
+            chunk.settings.layer_id == 0u16
+        })
+    {
+        // `Chunk::map_entity` is the chunk's layer
+        let layer = match layers.get(chunk.map_entity) {
+            Ok(layer) => layer,
+            Err(_) => continue,
+        };
+        let coords = match TileCoords::for_layer(
+            chunk.map_entity,
+            layer,
+            &transforms,
+            rapier_config.scale,
+        ) {
+            Some(coords) => coords,
+            None => continue,
+        };
+        let collision_grid = find_collision_grid(
+            chunk.map_entity,
+            &transforms,
+            &int_grids,
+        );
+
+        let size = chunk.settings.size;
+        let chunk_origin = chunk.settings.position * size;
+        let mut merged_tiles =
+            vec![None; (size.x * size.y) as usize];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let tile_entity = match chunk
+                    .get_tile_entity(UVec2::new(x, y))
+                {
+                    Some(tile_entity) => tile_entity,
+                    None => continue,
+                };
+                let (tile, tile_pos, processed) =
+                    match tiles.get(tile_entity) {
+                        Ok(tile) => tile,
+                        Err(_) => continue,
+                    };
+                let definition = match tile_behaviours
+                    .get(tile.texture_index)
+                {
+                    Some(definition) => definition,
+                    None => continue,
+                };
+                if is_merged_tile(definition) {
+                    if collision_grid.is_none() {
+                        merged_tiles
+                            [(y * size.x + x) as usize] =
+                            Some(definition.behaviour);
+                    }
+                    continue;
+                }
+                if processed.is_some() {
+                    continue;
+                }
+
+                let shape = definition.collider.unwrap_or(
+                    TileCollider {
+                        half_extents: layer
+                            .settings
+                            .tile_size
+                            / 2.0,
+                        offset: Vec2::ZERO,
+                    },
+                );
+                let half_extents = coords
+                    .pixels_to_physics(shape.half_extents);
+                let position = coords
+                    .tile_to_physics(*tile_pos)
+                    + coords
+                        .pixels_to_physics(shape.offset);
+                let collider_type =
+                    if is_solid(definition.behaviour) {
+                        ColliderType::Solid
+                    } else {
+                        ColliderType::Sensor
+                    };
+                let collider = ColliderBundle {
+                    shape: ColliderShape::cuboid(
+                        half_extents.x,
+                        half_extents.y,
+                    ),
+                    collider_type,
+                    material: ColliderMaterial {
+                        restitution: 0.0,
+                        friction: 0.0,
+                        ..Default::default()
+                    },
+                    position: ColliderPosition(
+                        Isometry2::new(
+                            Vector2::new(
+                                position.x, position.y,
+                            ),
+                            0.0,
+                        ),
+                    ),
+                    ..Default::default()
+                };
+                let mut tile_commands =
+                    commands.entity(tile_entity);
+                tile_commands
+                    .insert_bundle(collider)
+                    .insert_bundle(SpriteBundle {
+                        material: materials
+                            .add(Color::NONE.into()),
+                        sprite: Sprite::new(
+                            layer.settings.tile_size,
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(ColliderPositionSync::Discrete)
+                    .insert(ProcessedTile);
+                match definition.behaviour {
+                    TileBehaviour::Spike => {
+                        tile_commands.insert(Spike);
+                    }
+                    TileBehaviour::Spring => {
+                        match definition.spring {
+                            Some(strength) => {
+                                tile_commands.insert(
+                                    Spring::new(strength),
+                                );
+                            }
+                            None => warn!(
+                                "spring tile {} has no strength, it won't launch",
+                                tile.texture_index
+                            ),
+                        }
+                    }
+                    TileBehaviour::Checkpoint => {
+                        tile_commands.insert(Checkpoint);
+                    }
+                    TileBehaviour::Crumbling => {
+                        tile_commands.insert(CrumblingTile);
+                    }
+                    TileBehaviour::Solid
+                    | TileBehaviour::OneWay
+                    | TileBehaviour::Ladder => {}
+                }
+            }
+        }
+
+        if let Some(collision_grid) = collision_grid {
+            merged_tiles = collision_grid_tiles(
+                collision_grid,
+                chunk_origin,
+                size,
+            );
+        }
+
+        // remeshing marks the chunk as changed as well, only
+        // rebuild when the merged tiles actually differ.
+        if let Some(chunk_colliders) = &chunk_colliders {
+            if chunk_colliders.tiles == merged_tiles {
+                continue;
+            }
+        }
+
+        let colliders = merge_tiles(size, &merged_tiles)
+            .into_iter()
+            .map(|(behaviour, rect)| {
+                let min = chunk_origin + rect.min;
+                let max = min + rect.size - UVec2::ONE;
+                let position = (coords
+                    .tile_to_physics(min)
+                    + coords.tile_to_physics(max))
+                    / 2.0;
+                let half_extents = coords
+                    .pixels_to_physics(
+                        rect.size.as_f32()
+                            * coords.tile_size
+                            / 2.0,
+                    );
+                // one-way platforms are only collided
+                // with from above, see `OneWayHooks`
+                let one_way =
+                    behaviour == TileBehaviour::OneWay;
+                let active_hooks = if one_way {
+                    ActiveHooks::MODIFY_SOLVER_CONTACTS
+                } else {
+                    ActiveHooks::empty()
+                };
+                let mut collider_commands = commands
+                    .spawn_bundle(ColliderBundle {
+                        shape: ColliderShape::cuboid(
+                            half_extents.x,
+                            half_extents.y,
+                        ),
+                        flags: active_hooks.into(),
+                        material: ColliderMaterial {
+                            restitution: 0.0,
+                            friction: 0.0,
+                            ..Default::default()
+                        },
+                        position: ColliderPosition(
+                            Isometry2::new(
+                                Vector2::new(
+                                    position.x, position.y,
+                                ),
+                                0.0,
+                            ),
+                        ),
+                        ..Default::default()
+                    });
+                collider_commands.insert(ChunkCollider);
+                if one_way {
+                    collider_commands
+                        .insert(OneWayPlatform);
+                }
+                collider_commands.id()
+            })
+            .collect::<Vec<Entity>>();
+        // parent the colliders to the chunk so they are
+        // despawned along with the map.
+        commands.entity(entity).push_children(&colliders);
+
+        match chunk_colliders {
+            Some(mut chunk_colliders) => {
+                for collider in
+                    chunk_colliders.colliders.drain(..)
+                {
+                    commands
+                        .entity(collider)
+                        .despawn_recursive();
+                }
+                chunk_colliders.tiles = merged_tiles;
+                chunk_colliders.colliders = colliders;
+            }
+            None => {
+                commands.entity(entity).insert(
+                    ChunkColliders {
+                        tiles: merged_tiles,
+                        colliders,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const S: Option<TileBehaviour> =
+        Some(TileBehaviour::Solid);
+    const O: Option<TileBehaviour> =
+        Some(TileBehaviour::OneWay);
+    const E: Option<TileBehaviour> = None;
+
+    fn rect(min: (u32, u32), size: (u32, u32)) -> TileRect {
+        TileRect {
+            min: UVec2::new(min.0, min.1),
+            size: UVec2::new(size.0, size.1),
+        }
+    }
+
+    /// Every filled cell is covered by exactly one rectangle
+    /// of its own kind, and no empty cell is covered.
+    fn assert_exact_cover(
+        size: UVec2,
+        cells: &[Option<TileBehaviour>],
+        rects: &[(TileBehaviour, TileRect)],
+    ) {
+        let mut covered = vec![None; cells.len()];
+        for (kind, rect) in rects {
+            for y in rect.min.y..rect.min.y + rect.size.y {
+                for x in
+                    rect.min.x..rect.min.x + rect.size.x
+                {
+                    assert!(x < size.x && y < size.y);
+                    let index = (y * size.x + x) as usize;
+                    assert_eq!(covered[index], None);
+                    covered[index] = Some(*kind);
+                }
+            }
+        }
+        assert_eq!(covered, cells);
+    }
+
+    #[test]
+    fn merges_l_shape_into_two_rects() {
+        let size = UVec2::new(3, 3);
+        #[rustfmt::skip]
+        let cells = [
+            S, S, S,
+            S, E, E,
+            S, E, E,
+        ];
+        let rects = merge_tiles(size, &cells);
+        assert_eq!(
+            rects,
+            vec![
+                (
+                    TileBehaviour::Solid,
+                    rect((0, 0), (3, 1))
+                ),
+                (
+                    TileBehaviour::Solid,
+                    rect((0, 1), (1, 2))
+                ),
+            ]
+        );
+        assert_exact_cover(size, &cells, &rects);
+    }
+
+    #[test]
+    fn merges_full_chunk_into_one_rect() {
+        let size = UVec2::new(32, 32);
+        let cells = vec![S; 32 * 32];
+        let rects = merge_tiles(size, &cells);
+        assert_eq!(
+            rects,
+            vec![(
+                TileBehaviour::Solid,
+                rect((0, 0), (32, 32))
+            )]
+        );
+    }
+
+    #[test]
+    fn keeps_solid_and_one_way_apart() {
+        let size = UVec2::new(4, 3);
+        #[rustfmt::skip]
+        let cells = [
+            S, S, O, O,
+            S, S, O, E,
+            O, O, O, O,
+        ];
+        let rects = merge_tiles(size, &cells);
+        assert_eq!(
+            rects,
+            vec![
+                (
+                    TileBehaviour::Solid,
+                    rect((0, 0), (2, 2))
+                ),
+                (
+                    TileBehaviour::OneWay,
+                    rect((2, 0), (2, 1))
+                ),
+                (
+                    TileBehaviour::OneWay,
+                    rect((2, 1), (1, 2))
+                ),
+                (
+                    TileBehaviour::OneWay,
+                    rect((0, 2), (2, 1))
+                ),
+                (
+                    TileBehaviour::OneWay,
+                    rect((3, 2), (1, 1))
+                ),
+            ]
+        );
+        assert_exact_cover(size, &cells, &rects);
+    }
+
+    #[test]
+    fn merges_collision_grid_cells() {
+        // a 4x3 level with a one-way platform above solid
+        // ground, and a value without a collider
+        #[rustfmt::skip]
+        let values = vec![
+            0, 2, 2, 0,
+            0, 0, 0, 0,
+            1, 1, 1, 3,
+        ];
+        let collision_grid = LdtkIntGrid {
+            identifier: COLLISION_GRID_IDENTIFIER
+                .to_string(),
+            layer_id: 0,
+            size: UVec2::new(4, 3),
+            rows: 3,
+            grid_size: 18.0,
+            values,
+            map: Entity::new(0),
+        };
+
+        // flipped like the tiles, which leaves map row 0
+        // below the grid
+        let size = UVec2::new(4, 4);
+        let cells = collision_grid_tiles(
+            &collision_grid,
+            UVec2::ZERO,
+            size,
+        );
+        #[rustfmt::skip]
+        assert_eq!(cells, [
+            E, E, E, E,
+            S, S, S, E,
+            E, E, E, E,
+            E, O, O, E,
+        ]);
+        let rects = merge_tiles(size, &cells);
+        assert_eq!(
+            rects,
+            vec![
+                (
+                    TileBehaviour::Solid,
+                    rect((0, 1), (3, 1))
+                ),
+                (
+                    TileBehaviour::OneWay,
+                    rect((1, 3), (2, 1))
+                ),
+            ]
+        );
+        assert_exact_cover(size, &cells, &rects);
+
+        // chunks other than the first read their own cells
+        assert_eq!(
+            collision_grid_tiles(
+                &collision_grid,
+                UVec2::new(2, 2),
+                UVec2::new(2, 2),
+            ),
+            [E, E, O, E]
+        );
+    }
+
+    #[test]
+    fn collision_grid_overrides_merged_tiles_only() {
+        let tile = |behaviour| TileDefinition {
+            behaviour,
+            collider: None,
+            activated: None,
+            spring: None,
+        };
+        let solid = tile(TileBehaviour::Solid);
+        let crumbling = tile(TileBehaviour::Crumbling);
+        // one solid cell, at map row 1 once flipped
+        let collision_grid = LdtkIntGrid {
+            identifier: COLLISION_GRID_IDENTIFIER
+                .to_string(),
+            layer_id: 0,
+            size: UVec2::new(2, 1),
+            rows: 1,
+            grid_size: 18.0,
+            values: vec![1, 0],
+            map: Entity::new(0),
+        };
+        let grid = Some(&collision_grid);
+        let (cell, empty_cell) =
+            (UVec2::new(0, 1), UVec2::new(1, 1));
+
+        assert_eq!(
+            collision_behaviour(
+                Some(&solid),
+                None,
+                empty_cell
+            ),
+            Some(TileBehaviour::Solid)
+        );
+        assert_eq!(
+            collision_behaviour(
+                Some(&solid),
+                grid,
+                empty_cell
+            ),
+            None
+        );
+        assert_eq!(
+            collision_behaviour(None, grid, cell),
+            Some(TileBehaviour::Solid)
+        );
+        assert_eq!(
+            collision_behaviour(
+                Some(&crumbling),
+                grid,
+                empty_cell
+            ),
+            Some(TileBehaviour::Crumbling)
+        );
+    }
+
+    #[test]
+    fn empty_chunk_has_no_rects() {
+        let size = UVec2::new(32, 32);
+        let cells = vec![E; 32 * 32];
+        assert!(merge_tiles(size, &cells).is_empty());
+    }
+}
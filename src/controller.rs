@@ -0,0 +1,525 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{input::Action, state::GameState, Player};
+
+/// Tuning for how the corgi runs, jumps and falls. Speeds
+/// are in physics units per second, accelerations in
+/// physics units per second squared and times in seconds.
+#[derive(Debug, Clone)]
+pub struct PlayerControllerConfig {
+    pub max_run_speed: f32,
+    /// Acceleration towards the top speed while a direction
+    /// is held on the ground.
+    pub ground_acceleration: f32,
+    /// Deceleration on the ground with no direction held.
+    pub ground_friction: f32,
+    pub air_acceleration: f32,
+    pub air_friction: f32,
+    /// Upwards speed at the start of a jump.
+    pub jump_speed: f32,
+    /// How long after walking off a ledge a jump is still
+    /// allowed.
+    pub coyote_time: f32,
+    /// How long a jump pressed before landing is remembered.
+    pub jump_buffer_time: f32,
+    /// Upwards speed is multiplied by this when the jump key
+    /// is released early, for shorter hops.
+    pub jump_cut: f32,
+    pub max_fall_speed: f32,
+    /// Minimum upwards component of a contact normal for the
+    /// contact to count as ground.
+    pub min_ground_normal: f32,
+}
+
+impl Default for PlayerControllerConfig {
+    fn default() -> Self {
+        Self {
+            max_run_speed: 8.0,
+            ground_acceleration: 60.0,
+            ground_friction: 50.0,
+            air_acceleration: 30.0,
+            air_friction: 10.0,
+            jump_speed: 21.0,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.1,
+            jump_cut: 0.5,
+            max_fall_speed: 30.0,
+            min_ground_normal: 0.7,
+        }
+    }
+}
+
+/// Per corgi controller state.
+#[derive(Debug, Default)]
+pub struct PlayerController {
+    pub grounded: bool,
+    /// Time left to jump after leaving the ground.
+    coyote_timer: f32,
+    /// Time left for a buffered jump press to trigger.
+    jump_buffer_timer: f32,
+    /// Rising from a jump, so releasing the key cuts it
+    /// short.
+    jumping: bool,
+    /// Velocity of the ground the corgi is standing on, such
+    /// as a moving platform. Running is relative to it.
+    pub ground_velocity: Vec2,
+    /// Standing on a one-way platform, where jumping with
+    /// down held drops through it instead.
+    pub on_one_way_platform: bool,
+}
+
+impl PlayerController {
+    /// Advances coyote time, the jump buffer and cutting
+    /// jumps short by a frame, returning the new upwards
+    /// speed.
+    fn update_jump(
+        &mut self,
+        config: &PlayerControllerConfig,
+        jump_pressed: bool,
+        jump_held: bool,
+        mut speed: f32,
+        delta: f32,
+    ) -> f32 {
+        if self.grounded {
+            self.coyote_timer = config.coyote_time;
+        } else {
+            self.coyote_timer -= delta;
+        }
+        if jump_pressed {
+            self.jump_buffer_timer =
+                config.jump_buffer_time;
+        } else {
+            self.jump_buffer_timer -= delta;
+        }
+
+        if self.jump_buffer_timer > 0.0
+            && self.coyote_timer > 0.0
+        {
+            speed = config.jump_speed;
+            self.jump_buffer_timer = 0.0;
+            self.coyote_timer = 0.0;
+            self.jumping = true;
+        } else if self.jumping {
+            if speed <= 0.0 {
+                self.jumping = false;
+            } else if !jump_held {
+                speed *= config.jump_cut;
+                self.jumping = false;
+            }
+        }
+        speed.max(-config.max_fall_speed)
+    }
+}
+
+/// Horizontal speed after a frame of moving towards the
+/// held `direction`'s top speed, or towards standing still
+/// when `direction` is 0.0.
+fn run_speed(
+    config: &PlayerControllerConfig,
+    controller: &PlayerController,
+    direction: f32,
+    speed: f32,
+    delta: f32,
+) -> f32 {
+    let acceleration =
+        match (direction != 0.0, controller.grounded) {
+            (true, true) => config.ground_acceleration,
+            (true, false) => config.air_acceleration,
+            (false, true) => config.ground_friction,
+            (false, false) => config.air_friction,
+        };
+    let target = direction * config.max_run_speed
+        + controller.ground_velocity.x;
+    let max_change = acceleration * delta;
+    speed + (target - speed).clamp(-max_change, max_change)
+}
+
+/// The corgi is grounded when it has an active contact whose
+/// normal points up, out of the surface it is standing on.
+fn detect_ground(
+    narrow_phase: Res<NarrowPhase>,
+    config: Res<PlayerControllerConfig>,
+    mut players: Query<
+        (Entity, &mut PlayerController),
+        With<Player>,
+    >,
+) {
+    for (entity, mut controller) in players.iter_mut() {
+        let handle = entity.handle();
+        controller.grounded = narrow_phase
+            .contacts_with(handle)
+            .filter(|contact_pair| {
+                contact_pair.has_any_active_contact
+            })
+            .any(|contact_pair| {
+                contact_pair.manifolds.iter().any(
+                    |manifold| {
+                        // the normal points from collider1 to
+                        // collider2
+                        let up = if contact_pair.collider1
+                            == handle
+                        {
+                            -manifold.data.normal.y
+                        } else {
+                            manifold.data.normal.y
+                        };
+                        !manifold
+                            .data
+                            .solver_contacts
+                            .is_empty()
+                            && up
+                                >= config.min_ground_normal
+                    },
+                )
+            });
+    }
+}
+
+/// Runs in the held direction, see `run_speed`.
+fn run(
+    time: Res<Time>,
+    actions: Res<Input<Action>>,
+    config: Res<PlayerControllerConfig>,
+    mut players: Query<
+        (
+            &PlayerController,
+            &mut RigidBodyVelocity,
+            &mut TextureAtlasSprite,
+        ),
+        With<Player>,
+    >,
+) {
+    let mut direction = 0.0;
+    if actions.pressed(Action::MoveLeft) {
+        direction -= 1.0;
+    }
+    if actions.pressed(Action::MoveRight) {
+        direction += 1.0;
+    }
+
+    for (controller, mut velocity, mut sprite) in
+        players.iter_mut()
+    {
+        velocity.linvel.x = run_speed(
+            &config,
+            controller,
+            direction,
+            velocity.linvel.x,
+            time.delta_seconds(),
+        );
+
+        if direction < 0.0 {
+            sprite.flip_x = false;
+        } else if direction > 0.0 {
+            sprite.flip_x = true;
+        }
+    }
+}
+
+fn jump(
+    time: Res<Time>,
+    actions: Res<Input<Action>>,
+    config: Res<PlayerControllerConfig>,
+    mut players: Query<
+        (&mut PlayerController, &mut RigidBodyVelocity),
+        With<Player>,
+    >,
+) {
+    for (mut controller, mut velocity) in players.iter_mut()
+    {
+        // down and jump drop through one-way platforms
+        // instead, see `one_way::drop_through`
+        let dropping = controller.on_one_way_platform
+            && actions.pressed(Action::MoveDown);
+        velocity.linvel.y = controller.update_jump(
+            &config,
+            actions.just_pressed(Action::Jump) && !dropping,
+            actions.pressed(Action::Jump),
+            velocity.linvel.y,
+            time.delta_seconds(),
+        );
+    }
+}
+
+pub struct PlayerControllerPlugin;
+
+impl Plugin for PlayerControllerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PlayerControllerConfig>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        detect_ground
+                            .system()
+                            .label("detect_ground"),
+                    )
+                    .with_system(
+                        run.system()
+                            .label("run")
+                            .after("detect_ground"),
+                    )
+                    .with_system(
+                        jump.system()
+                            .label("jump")
+                            .after("detect_ground"),
+                    ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::Stage;
+
+    use super::*;
+
+    const DELTA: f32 = 1.0 / 60.0;
+
+    /// Steps the jump logic a frame at a time, with gravity
+    /// left out so the speeds stay easy to follow.
+    struct Jumper {
+        config: PlayerControllerConfig,
+        controller: PlayerController,
+        speed: f32,
+    }
+
+    impl Jumper {
+        fn new(grounded: bool) -> Self {
+            Self {
+                config: PlayerControllerConfig::default(),
+                controller: PlayerController {
+                    grounded,
+                    ..Default::default()
+                },
+                speed: 0.0,
+            }
+        }
+
+        fn frame(&mut self, pressed: bool, held: bool) {
+            self.speed = self.controller.update_jump(
+                &self.config,
+                pressed,
+                held,
+                self.speed,
+                DELTA,
+            );
+        }
+
+        /// Frames until the jump starts, if it does within
+        /// a second of pressing jump now.
+        fn press(&mut self) -> Option<usize> {
+            self.frame(true, true);
+            (0..60).find(|_| {
+                if self.speed == self.config.jump_speed {
+                    return true;
+                }
+                self.frame(false, true);
+                false
+            })
+        }
+    }
+
+    #[test]
+    fn jumps_from_the_ground_only() {
+        assert_eq!(Jumper::new(true).press(), Some(0));
+        let mut jumper = Jumper::new(false);
+        jumper.frame(false, false);
+        assert_eq!(jumper.press(), None);
+    }
+
+    #[test]
+    fn jumps_shortly_after_leaving_the_ground() {
+        let mut jumper = Jumper::new(true);
+        jumper.frame(false, false);
+        jumper.controller.grounded = false;
+        // five frames is within the 0.1 s of coyote time
+        for _ in 0..4 {
+            jumper.frame(false, false);
+        }
+        assert_eq!(jumper.press(), Some(0));
+
+        let mut jumper = Jumper::new(true);
+        jumper.frame(false, false);
+        jumper.controller.grounded = false;
+        for _ in 0..7 {
+            jumper.frame(false, false);
+        }
+        assert_eq!(jumper.press(), None);
+    }
+
+    #[test]
+    fn remembers_a_jump_pressed_just_before_landing() {
+        let mut jumper = Jumper::new(false);
+        jumper.frame(false, false);
+        jumper.frame(true, true);
+        for _ in 0..3 {
+            jumper.frame(false, true);
+        }
+        assert_eq!(jumper.speed, 0.0);
+        jumper.controller.grounded = true;
+        jumper.frame(false, true);
+        assert_eq!(jumper.speed, jumper.config.jump_speed);
+
+        // pressed too early, landing doesn't jump
+        let mut jumper = Jumper::new(false);
+        jumper.frame(false, false);
+        jumper.frame(true, true);
+        for _ in 0..10 {
+            jumper.frame(false, true);
+        }
+        jumper.controller.grounded = true;
+        jumper.frame(false, true);
+        assert_eq!(jumper.speed, 0.0);
+    }
+
+    #[test]
+    fn releasing_jump_cuts_the_jump_once() {
+        let mut jumper = Jumper::new(true);
+        assert_eq!(jumper.press(), Some(0));
+        jumper.controller.grounded = false;
+        jumper.frame(false, true);
+        assert_eq!(jumper.speed, jumper.config.jump_speed);
+        jumper.frame(false, false);
+        let cut = jumper.config.jump_speed
+            * jumper.config.jump_cut;
+        assert_eq!(jumper.speed, cut);
+        jumper.frame(false, false);
+        assert_eq!(jumper.speed, cut);
+    }
+
+    #[test]
+    fn caps_the_fall_speed() {
+        let mut jumper = Jumper::new(false);
+        jumper.speed = -100.0;
+        jumper.frame(false, false);
+        assert_eq!(
+            jumper.speed,
+            -jumper.config.max_fall_speed
+        );
+    }
+
+    #[test]
+    fn running_accelerates_to_the_top_speed() {
+        let config = PlayerControllerConfig::default();
+        let grounded = PlayerController {
+            grounded: true,
+            ..Default::default()
+        };
+        let mut speed = 0.0;
+        let mut frames = 0;
+        while speed < config.max_run_speed {
+            speed = run_speed(
+                &config, &grounded, 1.0, speed, DELTA,
+            );
+            frames += 1;
+            assert!(speed <= config.max_run_speed);
+        }
+        // 8 units per second at 60 per second squared
+        assert_eq!(frames, 8);
+
+        // friction stops the corgi without overshooting
+        while speed > 0.0 {
+            speed = run_speed(
+                &config, &grounded, 0.0, speed, DELTA,
+            );
+        }
+        assert_eq!(speed, 0.0);
+    }
+
+    #[test]
+    fn air_control_is_weaker_and_runs_relative_to_the_ground(
+    ) {
+        let config = PlayerControllerConfig::default();
+        let airborne = PlayerController::default();
+        assert_eq!(
+            run_speed(&config, &airborne, -1.0, 0.0, 0.1),
+            -config.air_acceleration * 0.1
+        );
+
+        let on_platform = PlayerController {
+            grounded: true,
+            ground_velocity: Vec2::new(3.0, 0.0),
+            ..Default::default()
+        };
+        // standing still on a moving platform moves with it
+        assert_eq!(
+            run_speed(
+                &config,
+                &on_platform,
+                0.0,
+                3.0,
+                DELTA
+            ),
+            3.0
+        );
+        assert_eq!(
+            run_speed(
+                &config,
+                &on_platform,
+                1.0,
+                11.0,
+                DELTA
+            ),
+            3.0 + config.max_run_speed
+        );
+    }
+
+    /// Runs the jump system once for a grounded corgi with
+    /// the given actions pressed, returning its upwards
+    /// speed.
+    fn jump_once(
+        on_one_way_platform: bool,
+        pressed: &[Action],
+    ) -> f32 {
+        let mut world = World::default();
+        world.insert_resource(Time::default());
+        world.insert_resource(
+            PlayerControllerConfig::default(),
+        );
+        let mut actions = Input::<Action>::default();
+        for action in pressed {
+            actions.press(*action);
+        }
+        world.insert_resource(actions);
+        let controller = PlayerController {
+            grounded: true,
+            on_one_way_platform,
+            ..Default::default()
+        };
+        let player = world
+            .spawn()
+            .insert_bundle((
+                Player,
+                controller,
+                RigidBodyVelocity::default(),
+            ))
+            .id();
+
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(jump.system());
+        stage.run(&mut world);
+        world
+            .get::<RigidBodyVelocity>(player)
+            .unwrap()
+            .linvel
+            .y
+    }
+
+    #[test]
+    fn down_and_jump_on_a_one_way_platform_drops_instead() {
+        let jump_speed =
+            PlayerControllerConfig::default().jump_speed;
+        let down_jump = [Action::MoveDown, Action::Jump];
+        assert_eq!(jump_once(true, &down_jump), 0.0);
+        assert_eq!(
+            jump_once(true, &[Action::Jump]),
+            jump_speed
+        );
+        // solid ground can't be dropped through
+        assert_eq!(
+            jump_once(false, &down_jump),
+            jump_speed
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+/// Converts between tile positions, world pixels and
+/// physics units for a single map layer.
+///
+/// Tile positions are the `UVec2` components on tile
+/// entities, world pixels are bevy `Transform` space and
+/// physics units are rapier space, which is world pixels
+/// divided by `RapierConfiguration::scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileCoords {
+    /// World position of the bottom left corner of tile
+    /// (0, 0).
+    pub origin: Vec2,
+    /// Size of a tile in world pixels.
+    pub tile_size: Vec2,
+    /// World pixels per physics unit.
+    pub physics_scale: f32,
+}
+
+impl TileCoords {
+    /// Builds the conversion for a layer from the map's
+    /// `Transform`, the layer's local `Transform` (the ldtk
+    /// loader offsets layers by the level height) and
+    /// `LayerSettings::tile_size`.
+    pub fn new(
+        map_transform: &Transform,
+        layer_transform: &Transform,
+        tile_size: Vec2,
+        physics_scale: f32,
+    ) -> Self {
+        let layer_world =
+            map_transform.mul_transform(*layer_transform);
+        Self {
+            origin: layer_world.translation.truncate(),
+            tile_size: tile_size
+                * layer_world.scale.truncate(),
+            physics_scale,
+        }
+    }
+
+    /// Builds the conversion for a layer entity, finding the
+    /// map through the layer's `Parent`.
+    pub fn for_layer(
+        layer_entity: Entity,
+        layer: &Layer,
+        transforms: &Query<(&Transform, Option<&Parent>)>,
+        physics_scale: f32,
+    ) -> Option<Self> {
+        let (layer_transform, map_entity) =
+            transforms.get(layer_entity).ok()?;
+        let (map_transform, _) =
+            transforms.get(map_entity?.0).ok()?;
+        Some(Self::new(
+            map_transform,
+            layer_transform,
+            layer.settings.tile_size,
+            physics_scale,
+        ))
+    }
+
+    /// World position of the center of a tile.
+    pub fn tile_to_world(&self, tile_pos: UVec2) -> Vec2 {
+        self.origin
+            + (tile_pos.as_f32() + Vec2::splat(0.5))
+                * self.tile_size
+    }
+
+    /// The tile containing a world position, `None` if the
+    /// position is left of or below the layer.
+    pub fn world_to_tile(
+        &self,
+        world: Vec2,
+    ) -> Option<UVec2> {
+        let tile_pos = ((world - self.origin)
+            / self.tile_size)
+            .floor();
+        if tile_pos.x < 0.0 || tile_pos.y < 0.0 {
+            None
+        } else {
+            Some(tile_pos.as_u32())
+        }
+    }
+
+    pub fn world_to_physics(&self, world: Vec2) -> Vec2 {
+        world / self.physics_scale
+    }
+
+    pub fn physics_to_world(&self, physics: Vec2) -> Vec2 {
+        physics * self.physics_scale
+    }
+
+    /// Physics position of the center of a tile.
+    pub fn tile_to_physics(&self, tile_pos: UVec2) -> Vec2 {
+        self.world_to_physics(self.tile_to_world(tile_pos))
+    }
+
+    pub fn physics_to_tile(
+        &self,
+        physics: Vec2,
+    ) -> Option<UVec2> {
+        self.world_to_tile(self.physics_to_world(physics))
+    }
+
+    /// Converts a size or offset in pixels to physics
+    /// units.
+    pub fn pixels_to_physics(&self, pixels: Vec2) -> Vec2 {
+        pixels / self.physics_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords() -> TileCoords {
+        TileCoords::new(
+            &Transform::from_xyz(-960.0, 436.0, 0.0),
+            &Transform::from_xyz(0.0, -706.0, 1.0),
+            Vec2::new(18.0, 18.0),
+            25.0,
+        )
+    }
+
+    #[test]
+    fn origin_includes_layer_offset() {
+        let coords = coords();
+        assert_eq!(
+            coords.origin,
+            Vec2::new(-960.0, -270.0)
+        );
+        assert_eq!(
+            coords.tile_to_world(UVec2::new(0, 0)),
+            Vec2::new(-951.0, -261.0)
+        );
+    }
+
+    #[test]
+    fn tile_world_round_trip() {
+        let coords = coords();
+        for x in 0..80 {
+            for y in 0..40 {
+                let tile_pos = UVec2::new(x, y);
+                let world = coords.tile_to_world(tile_pos);
+                assert_eq!(
+                    coords.world_to_tile(world),
+                    Some(tile_pos)
+                );
+                // any point inside the tile maps back to it
+                let corner = world - Vec2::splat(8.9);
+                assert_eq!(
+                    coords.world_to_tile(corner),
+                    Some(tile_pos)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tile_physics_round_trip() {
+        let coords = coords();
+        let tile_pos = UVec2::new(42, 7);
+        let physics = coords.tile_to_physics(tile_pos);
+        assert_eq!(
+            coords.physics_to_world(physics),
+            coords.tile_to_world(tile_pos)
+        );
+        assert_eq!(
+            coords.physics_to_tile(physics),
+            Some(tile_pos)
+        );
+    }
+
+    #[test]
+    fn outside_layer_has_no_tile() {
+        let coords = coords();
+        assert_eq!(
+            coords.world_to_tile(Vec2::new(-961.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            coords.world_to_tile(Vec2::new(0.0, -271.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn respects_grid_size_and_scale() {
+        let coords = TileCoords::new(
+            &Transform::from_xyz(100.0, 50.0, 0.0),
+            &Transform::identity(),
+            Vec2::new(16.0, 16.0),
+            10.0,
+        );
+        assert_eq!(
+            coords.tile_to_physics(UVec2::new(2, 1)),
+            Vec2::new(14.0, 7.4)
+        );
+        assert_eq!(
+            coords.pixels_to_physics(Vec2::new(8.0, 8.0)),
+            Vec2::new(0.8, 0.8)
+        );
+    }
+}
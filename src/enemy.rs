@@ -0,0 +1,347 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    colliders::{collision_behaviour, find_collision_grid},
+    coords::TileCoords,
+    ldtk_entities::EntitySprites,
+    state::GameState,
+    tile_behaviour::{
+        TileBehaviour, TileBehaviourTable, TileBehaviours,
+    },
+    DeathCause, Player, PlayerDied,
+};
+
+/// Identifier of the LDtk entity enemies are spawned from.
+const ENEMY_IDENTIFIER: &str = "Enemy";
+
+/// Tuning for enemies.
+#[derive(Debug, Clone)]
+pub struct EnemyConfig {
+    /// Walking speed, in physics units per second.
+    pub speed: f32,
+    /// Half size of an enemy's collider, in pixels.
+    pub half_extents: Vec2,
+    /// Upwards impulse given to the corgi when it stomps on
+    /// an enemy, in physics units.
+    pub stomp_impulse: f32,
+    /// Smallest upwards component of the contact normal for
+    /// a contact to count as a stomp rather than running
+    /// into the enemy.
+    pub min_stomp_normal: f32,
+}
+
+impl Default for EnemyConfig {
+    fn default() -> Self {
+        Self {
+            speed: 2.0,
+            half_extents: Vec2::new(7.0, 7.0),
+            stomp_impulse: 4.0,
+            min_stomp_normal: 0.7,
+        }
+    }
+}
+
+/// An enemy walking back and forth along the ground.
+pub struct Enemy {
+    /// -1.0 when walking left, 1.0 when walking right.
+    direction: f32,
+}
+
+/// Gives the enemies loaded from the level a body, a
+/// collider and the sprite LDtk shows them with.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+fn setup_enemies(
+    mut commands: Commands,
+    config: Res<EnemyConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    ldtk_maps: Res<Assets<LdtkMap>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut sprites: Local<EntitySprites>,
+    maps: Query<&Handle<LdtkMap>>,
+    enemies: Query<
+        (Entity, &LdtkEntity, &Transform),
+        Added<Enemy>,
+    >,
+) {
+    for (entity, ldtk_entity, transform) in enemies.iter() {
+        let (texture_atlas, index) = match sprites.tile(
+            ldtk_entity,
+            &maps,
+            &ldtk_maps,
+            &mut texture_atlases,
+        ) {
+            Some(tile) => tile,
+            None => {
+                warn!(
+                    "enemy at {:?} has no tile, skipping",
+                    ldtk_entity.px
+                );
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        let world = transform.translation.truncate();
+        let position = world / rapier_config.scale;
+        let half_extents =
+            config.half_extents / rapier_config.scale;
+
+        commands
+            .entity(entity)
+            .insert_bundle(RigidBodyBundle {
+                position: position.into(),
+                forces: RigidBodyForces {
+                    gravity_scale: 10.0,
+                    ..Default::default()
+                },
+                mass_properties: RigidBodyMassProps {
+                    flags: RigidBodyMassPropsFlags::ROTATION_LOCKED,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(
+                    half_extents.x,
+                    half_extents.y,
+                ),
+                material: ColliderMaterial {
+                    restitution: 0.0,
+                    friction: 0.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert_bundle(SpriteSheetBundle {
+                texture_atlas,
+                sprite: TextureAtlasSprite::new(index),
+                transform: Transform::from_translation(
+                    world.extend(1.0),
+                ),
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete);
+    }
+}
+
+/// Walks enemies along the ground, turning them around at
+/// walls and at the edges of ledges. Walls and ground are
+/// read the same way as the level's colliders are built,
+/// including the collision grid.
+#[allow(clippy::too_many_arguments)]
+fn patrol(
+    config: Res<EnemyConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    tile_behaviour_table: Res<TileBehaviourTable>,
+    tile_behaviours: Res<Assets<TileBehaviours>>,
+    transforms: Query<(&Transform, Option<&Parent>)>,
+    tiles: Query<&Tile>,
+    int_grids: Query<&LdtkIntGrid>,
+    mut enemies: Query<(
+        &mut Enemy,
+        &RigidBodyPosition,
+        &mut RigidBodyVelocity,
+    )>,
+    map_query: MapQuery,
+) {
+    let tile_behaviours = match tile_behaviours
+        .get(&tile_behaviour_table.0)
+    {
+        Some(tile_behaviours) => tile_behaviours,
+        None => return,
+    };
+    let (layer_entity, layer) =
+        match map_query.get_layer(0u16, 0u16) {
+            Some(layer) => layer,
+            None => return,
+        };
+    let coords = match TileCoords::for_layer(
+        layer_entity,
+        layer,
+        &transforms,
+        rapier_config.scale,
+    ) {
+        Some(coords) => coords,
+        None => return,
+    };
+    let collision_grid = find_collision_grid(
+        layer_entity,
+        &transforms,
+        &int_grids,
+    );
+    let behaviour =
+        |tile_pos: UVec2, tile_entity: Option<Entity>| {
+            let definition = tile_entity
+                .and_then(|tile_entity| {
+                    tiles.get(tile_entity).ok()
+                })
+                .and_then(|tile| {
+                    tile_behaviours.get(tile.texture_index)
+                });
+            collision_behaviour(
+                definition,
+                collision_grid,
+                tile_pos,
+            )
+        };
+
+    for (mut enemy, position, mut velocity) in
+        enemies.iter_mut()
+    {
+        let translation = position.position.translation;
+        let physics =
+            Vec2::new(translation.x, translation.y);
+        if let Some(tile_pos) =
+            coords.physics_to_tile(physics)
+        {
+            let neighbors = map_query
+                .get_tile_neighbors(tile_pos, 0u16, 0u16);
+            let neighbor = |x: i32, y: i32| {
+                let target = IVec2::new(
+                    tile_pos.x as i32 + x,
+                    tile_pos.y as i32 + y,
+                );
+                if target.x < 0 || target.y < 0 {
+                    return None;
+                }
+                let tile_entity = neighbors
+                    .iter()
+                    .find(|(pos, _)| *pos == target)
+                    .and_then(|(_, tile_entity)| {
+                        *tile_entity
+                    });
+                behaviour(target.as_u32(), tile_entity)
+            };
+            let is_ground = |behaviour| {
+                matches!(
+                    behaviour,
+                    Some(TileBehaviour::Solid)
+                        | Some(TileBehaviour::OneWay)
+                        | Some(TileBehaviour::Crumbling)
+                )
+            };
+            let ahead = enemy.direction as i32;
+            let wall = matches!(
+                neighbor(ahead, 0),
+                Some(TileBehaviour::Solid)
+                    | Some(TileBehaviour::Crumbling)
+            );
+            // only look for ledges while walking on the
+            // ground, not while falling
+            let ledge = is_ground(neighbor(0, -1))
+                && !is_ground(neighbor(ahead, -1));
+            // walk up to the middle of the tile before
+            // turning so enemies use all of the ground
+            let past_middle = (physics
+                - coords.tile_to_physics(tile_pos))
+            .x * enemy.direction
+                >= 0.0;
+            if past_middle && (wall || ledge) {
+                enemy.direction = -enemy.direction;
+            }
+        }
+        velocity.linvel.x = enemy.direction * config.speed;
+    }
+}
+
+/// Stomping on an enemy from above defeats it and bounces
+/// the corgi off it, running into it from any other side
+/// kills the corgi.
+fn enemy_contact(
+    mut commands: Commands,
+    config: Res<EnemyConfig>,
+    narrow_phase: Res<NarrowPhase>,
+    mut player: Query<
+        (
+            Entity,
+            &mut RigidBodyVelocity,
+            &RigidBodyMassProps,
+        ),
+        With<Player>,
+    >,
+    enemies: Query<(), With<Enemy>>,
+    mut player_died: EventWriter<PlayerDied>,
+) {
+    let (player_entity, mut velocity, mass) =
+        match player.single_mut() {
+            Ok(player) => player,
+            Err(_) => return,
+        };
+    let handle = player_entity.handle();
+
+    let mut stomped = false;
+    let mut hit = false;
+    for contact_pair in narrow_phase
+        .contacts_with(handle)
+        .filter(|contact_pair| {
+            contact_pair.has_any_active_contact
+        })
+    {
+        let other = if contact_pair.collider1 == handle {
+            contact_pair.collider2
+        } else {
+            contact_pair.collider1
+        };
+        let enemy = other.entity();
+        if enemies.get(enemy).is_err() {
+            continue;
+        }
+        let stomp =
+            contact_pair.manifolds.iter().any(|manifold| {
+                // the normal points from collider1 to
+                // collider2
+                let up = if contact_pair.collider1 == handle
+                {
+                    -manifold.data.normal.y
+                } else {
+                    manifold.data.normal.y
+                };
+                up >= config.min_stomp_normal
+            });
+        if stomp {
+            commands.entity(enemy).despawn_recursive();
+            stomped = true;
+        } else {
+            hit = true;
+        }
+    }
+
+    if stomped {
+        // bounce the same height however fast the corgi was
+        // falling
+        velocity.linvel.y = 0.0;
+        velocity.apply_impulse(
+            mass,
+            Vec2::new(0.0, config.stomp_impulse).into(),
+        );
+    } else if hit {
+        player_died.send(PlayerDied {
+            cause: DeathCause::Enemy,
+        });
+    }
+}
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<EnemyConfig>()
+            .register_ldtk_entity(ENEMY_IDENTIFIER, |_| {
+                Enemy { direction: -1.0 }
+            })
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(setup_enemies.system())
+                    .with_system(patrol.system())
+                    .with_system(
+                        enemy_contact
+                            .system()
+                            .before("lose_life"),
+                    ),
+            );
+    }
+}
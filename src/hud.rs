@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+
+use crate::{
+    levels::{LevelProgress, LEVELS},
+    menu::FONT,
+    score::{LevelTimer, Lives, Score},
+    state::GameState,
+};
+
+/// Root node of the HUD.
+pub struct Hud;
+
+/// What a piece of HUD text shows.
+#[derive(Debug, Clone, Copy)]
+pub enum HudText {
+    Coins,
+    Lives,
+    Level,
+    Timer,
+}
+
+fn spawn_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load(FONT);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(
+                    Val::Percent(100.0),
+                    Val::Auto,
+                ),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    ..Default::default()
+                },
+                justify_content:
+                    JustifyContent::SpaceBetween,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(Hud)
+        .with_children(|parent| {
+            for hud_text in [
+                HudText::Coins,
+                HudText::Lives,
+                HudText::Level,
+                HudText::Timer,
+            ]
+            .iter()
+            {
+                parent
+                    .spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(
+                                16.0,
+                            )),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 32.0,
+                                color: Color::hex("3F4A5C")
+                                    .unwrap(),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(*hud_text);
+            }
+        });
+}
+
+fn update_hud(
+    score: Res<Score>,
+    lives: Res<Lives>,
+    level_timer: Res<LevelTimer>,
+    progress: Res<LevelProgress>,
+    mut texts: Query<(&HudText, &mut Text)>,
+) {
+    for (hud_text, mut text) in texts.iter_mut() {
+        let value = match hud_text {
+            HudText::Coins => format!("Coins: {}", score.0),
+            HudText::Lives => format!("Lives: {}", lives.0),
+            HudText::Level => {
+                LEVELS[progress.current].name.to_string()
+            }
+            HudText::Timer => {
+                let seconds = level_timer.0 as u32;
+                format!(
+                    "{}:{:02}",
+                    seconds / 60,
+                    seconds % 60
+                )
+            }
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn despawn_hud(
+    mut commands: Commands,
+    huds: Query<Entity, With<Hud>>,
+) {
+    for hud in huds.iter() {
+        commands.entity(hud).despawn_recursive();
+    }
+}
+
+/// Shows coins, lives, the level name and the level timer
+/// while playing.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(spawn_hud.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(update_hud.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Playing)
+                .with_system(despawn_hud.system()),
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fs};
+
+use bevy::{
+    input::InputSystem, prelude::*, utils::HashSet,
+};
+use serde::Deserialize;
+
+const BINDINGS_PATH: &str = "input.ron";
+
+/// What the player wants to do, independent of the key or
+/// button used. Read through `Res<Input<Action>>` the same
+/// way as `Input<KeyCode>`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize,
+)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Pause,
+    Confirm,
+    Back,
+}
+
+impl Action {
+    /// Read while playing a level.
+    fn in_game(self) -> bool {
+        !matches!(self, Action::Confirm | Action::Back)
+    }
+
+    /// Read by the menus.
+    fn in_menus(self) -> bool {
+        matches!(
+            self,
+            Action::Pause | Action::Confirm | Action::Back
+        )
+    }
+
+    /// Actions read at the same time can't share a binding.
+    fn shares_context(self, other: Action) -> bool {
+        self.in_game() && other.in_game()
+            || self.in_menus() && other.in_menus()
+    }
+}
+
+/// Which keys, gamepad buttons and gamepad axes trigger each
+/// action, loaded from `input.ron`.
+#[derive(Debug, Deserialize)]
+pub struct InputBindings {
+    pub keys: HashMap<Action, Vec<KeyCode>>,
+    pub buttons: HashMap<Action, Vec<GamepadButtonType>>,
+    /// An axis and the direction, -1.0 or 1.0, it has to be
+    /// pushed in.
+    pub axes: HashMap<Action, Vec<(GamepadAxisType, f32)>>,
+    pub axis_threshold: f32,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        ron::de::from_str(include_str!("../input.ron"))
+            .expect("default input bindings to be valid")
+    }
+}
+
+/// The first binding shared by two actions read in the
+/// same context.
+fn find_overlap<T: PartialEq + std::fmt::Debug>(
+    bindings: &HashMap<Action, Vec<T>>,
+) -> Option<String> {
+    bindings.iter().find_map(|(action, inputs)| {
+        bindings.iter().find_map(|(other, other_inputs)| {
+            if other == action
+                || !action.shares_context(*other)
+            {
+                return None;
+            }
+            inputs
+                .iter()
+                .find(|input| other_inputs.contains(input))
+                .map(|input| {
+                    format!(
+                        "{:?} is bound to both {:?} and {:?}",
+                        input, action, other
+                    )
+                })
+        })
+    })
+}
+
+impl InputBindings {
+    /// Fails if a key, button or axis triggers two actions
+    /// that are read at the same time.
+    fn check(&self) -> Result<(), anyhow::Error> {
+        match find_overlap(&self.keys)
+            .or_else(|| find_overlap(&self.buttons))
+            .or_else(|| find_overlap(&self.axes))
+        {
+            Some(overlap) => Err(anyhow::anyhow!(overlap)),
+            None => Ok(()),
+        }
+    }
+
+    fn load() -> Self {
+        match fs::read_to_string(BINDINGS_PATH)
+            .map_err(anyhow::Error::from)
+            .and_then(|bindings| {
+                ron::de::from_str(&bindings)
+                    .map_err(anyhow::Error::from)
+            })
+            .and_then(|bindings: Self| {
+                bindings.check().map(|_| bindings)
+            }) {
+            Ok(bindings) => bindings,
+            Err(error) => {
+                warn!(
+                    "failed to load input bindings, using the defaults: {}",
+                    error
+                );
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Connected gamepads and the actions that were held last
+/// frame.
+#[derive(Default)]
+struct ActionTracker {
+    gamepads: HashSet<Gamepad>,
+    held: HashSet<Action>,
+}
+
+fn update_actions(
+    mut tracker: Local<ActionTracker>,
+    mut gamepad_events: EventReader<GamepadEvent>,
+    bindings: Res<InputBindings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    button_input: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<Input<Action>>,
+) {
+    for GamepadEvent(gamepad, event) in
+        gamepad_events.iter()
+    {
+        match event {
+            GamepadEventType::Connected => {
+                tracker.gamepads.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                tracker.gamepads.remove(gamepad);
+            }
+            _ => {}
+        }
+    }
+
+    let gamepads = &tracker.gamepads;
+    let mut held = HashSet::default();
+    for (action, keys) in bindings.keys.iter() {
+        if keys
+            .iter()
+            .any(|key| keyboard_input.pressed(*key))
+        {
+            held.insert(*action);
+        }
+    }
+    for (action, buttons) in bindings.buttons.iter() {
+        if gamepads.iter().any(|gamepad| {
+            buttons.iter().any(|button| {
+                button_input.pressed(GamepadButton(
+                    *gamepad, *button,
+                ))
+            })
+        }) {
+            held.insert(*action);
+        }
+    }
+    for (action, bound_axes) in bindings.axes.iter() {
+        if gamepads.iter().any(|gamepad| {
+            bound_axes.iter().any(|(axis, direction)| {
+                axes.get(GamepadAxis(*gamepad, *axis))
+                    .map_or(false, |value| {
+                        value * direction
+                            > bindings.axis_threshold
+                    })
+            })
+        }) {
+            held.insert(*action);
+        }
+    }
+
+    // only press and release on changes, so an action reset
+    // by a system isn't pressed again while it is held.
+    actions.update();
+    for action in held.difference(&tracker.held) {
+        actions.press(*action);
+    }
+    for action in tracker.held.difference(&held) {
+        actions.release(*action);
+    }
+    tracker.held = held;
+}
+
+/// Forgets this frame's presses and releases, so the press
+/// that left a menu doesn't also act in the game. Held
+/// actions stay held.
+pub fn clear_actions(mut actions: ResMut<Input<Action>>) {
+    actions.update();
+}
+
+/// Maps keyboard and gamepad input to `Input<Action>`.
+pub struct ActionInputPlugin;
+
+impl Plugin for ActionInputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(InputBindings::load())
+            .init_resource::<Input<Action>>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_actions.system().after(InputSystem),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_do_not_overlap() {
+        assert!(InputBindings::default().check().is_ok());
+    }
+
+    #[test]
+    fn overlap_within_a_context_is_rejected() {
+        let mut bindings = InputBindings::default();
+        bindings
+            .keys
+            .insert(Action::Jump, vec![KeyCode::Up]);
+        assert!(bindings.check().is_err());
+
+        let mut bindings = InputBindings::default();
+        bindings.buttons.insert(
+            Action::Confirm,
+            vec![GamepadButtonType::Start],
+        );
+        assert!(bindings.check().is_err());
+    }
+
+    #[test]
+    fn game_and_menu_actions_can_share_bindings() {
+        // the press that leaves a menu is cleared before
+        // the level reads it, see `clear_actions`
+        let mut bindings = InputBindings::default();
+        bindings
+            .keys
+            .insert(Action::Confirm, vec![KeyCode::Space]);
+        assert!(bindings.check().is_ok());
+    }
+}
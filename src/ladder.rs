@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    controller::PlayerController,
+    coords::TileCoords,
+    input::Action,
+    state::GameState,
+    tile_behaviour::{
+        TileBehaviour, TileBehaviourTable, TileBehaviours,
+    },
+    Player,
+};
+
+/// Identifier of the LDtk layer holding the ladder tiles
+/// and the overlay hiding secret areas.
+const LADDER_LAYER_IDENTIFIER: &str = "Ladders_and_secrets";
+
+/// Id of the map layer loaded from the ladders and secrets
+/// LDtk layer, if the level has one.
+pub fn ladder_layer_id(
+    ldtk_layers: &Query<&LdtkLayer>,
+) -> Option<u16> {
+    ldtk_layers
+        .iter()
+        .find(|ldtk_layer| {
+            ldtk_layer.identifier == LADDER_LAYER_IDENTIFIER
+        })
+        .map(|ldtk_layer| ldtk_layer.layer_id)
+}
+
+/// Tuning for climbing ladders.
+#[derive(Debug, Clone)]
+pub struct LadderConfig {
+    /// Climbing speed, in physics units per second.
+    pub climb_speed: f32,
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        Self { climb_speed: 5.0 }
+    }
+}
+
+/// Added to the corgi while it is climbing a ladder.
+pub struct Climbing {
+    /// Gravity scale to restore once the corgi lets go.
+    gravity_scale: f32,
+}
+
+/// Grabs a ladder when up or down is held while the corgi
+/// overlaps one, then moves the corgi along it with gravity
+/// turned off.
+///
+/// Climbing counts as standing on the ground, so the corgi
+/// can run off the side of a ladder and jumping works as
+/// usual, letting go of the ladder.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+fn climb(
+    mut commands: Commands,
+    actions: Res<Input<Action>>,
+    config: Res<LadderConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    tile_behaviour_table: Res<TileBehaviourTable>,
+    tile_behaviours: Res<Assets<TileBehaviours>>,
+    transforms: Query<(&Transform, Option<&Parent>)>,
+    tiles: Query<&Tile>,
+    ldtk_layers: Query<&LdtkLayer>,
+    mut players: Query<
+        (
+            Entity,
+            &RigidBodyPosition,
+            &mut RigidBodyVelocity,
+            &mut RigidBodyForces,
+            &mut PlayerController,
+            Option<&Climbing>,
+        ),
+        With<Player>,
+    >,
+    map_query: MapQuery,
+) {
+    let tile_behaviours = match tile_behaviours
+        .get(&tile_behaviour_table.0)
+    {
+        Some(tile_behaviours) => tile_behaviours,
+        None => return,
+    };
+    let layer_id = match ladder_layer_id(&ldtk_layers) {
+        Some(layer_id) => layer_id,
+        None => return,
+    };
+    let coords = match map_query
+        .get_layer(0u16, layer_id)
+        .and_then(|(layer_entity, layer)| {
+            TileCoords::for_layer(
+                layer_entity,
+                layer,
+                &transforms,
+                rapier_config.scale,
+            )
+        }) {
+        Some(coords) => coords,
+        None => return,
+    };
+    let is_ladder = |tile_pos: UVec2| {
+        map_query
+            .get_tile_entity(tile_pos, 0u16, layer_id)
+            .ok()
+            .and_then(|tile_entity| {
+                tiles.get(tile_entity).ok()
+            })
+            .and_then(|tile| {
+                tile_behaviours.get(tile.texture_index)
+            })
+            .map_or(false, |definition| {
+                definition.behaviour
+                    == TileBehaviour::Ladder
+            })
+    };
+
+    let mut direction = 0.0;
+    if actions.pressed(Action::MoveUp) {
+        direction += 1.0;
+    }
+    if actions.pressed(Action::MoveDown) {
+        direction -= 1.0;
+    }
+    let jumped = actions.just_pressed(Action::Jump);
+
+    for (
+        entity,
+        position,
+        mut velocity,
+        mut forces,
+        mut controller,
+        climbing,
+    ) in players.iter_mut()
+    {
+        let translation = position.position.translation;
+        let physics =
+            Vec2::new(translation.x, translation.y);
+        let tile_pos = coords
+            .physics_to_tile(physics)
+            .filter(|tile_pos| is_ladder(*tile_pos));
+
+        let tile_pos = match (climbing, tile_pos) {
+            (Some(climbing), tile_pos)
+                if tile_pos.is_none() || jumped =>
+            {
+                forces.gravity_scale =
+                    climbing.gravity_scale;
+                commands
+                    .entity(entity)
+                    .remove::<Climbing>();
+                if jumped {
+                    // still grounded this frame so the jump
+                    // system jumps off the ladder
+                    controller.grounded = true;
+                }
+                continue;
+            }
+            (Some(_), Some(tile_pos)) => tile_pos,
+            (None, Some(tile_pos))
+                if direction != 0.0 && !jumped =>
+            {
+                commands.entity(entity).insert(Climbing {
+                    gravity_scale: forces.gravity_scale,
+                });
+                forces.gravity_scale = 0.0;
+                tile_pos
+            }
+            _ => continue,
+        };
+
+        // hold on at the top of the ladder instead of
+        // climbing off its end
+        let at_top = !is_ladder(tile_pos + UVec2::Y)
+            && physics.y
+                >= coords.tile_to_physics(tile_pos).y;
+        velocity.linvel.y = if direction > 0.0 && at_top {
+            0.0
+        } else {
+            direction * config.climb_speed
+        };
+        controller.grounded = true;
+    }
+}
+
+pub struct LadderPlugin;
+
+impl Plugin for LadderPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LadderConfig>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(
+                    climb
+                        .system()
+                        .after("detect_ground")
+                        .before("run")
+                        .before("jump"),
+                ),
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+/// Builds sprites for the tiles LDtk shows entities with,
+/// sharing one texture atlas per tileset. Kept in a `Local`
+/// so the atlases are reused across levels.
+#[derive(Default)]
+pub struct EntitySprites {
+    atlases: HashMap<
+        Handle<Texture>,
+        (Handle<TextureAtlas>, i64),
+    >,
+}
+
+impl EntitySprites {
+    /// The atlas and atlas index of an entity's tile, `None`
+    /// if it has no tile or the map hasn't loaded.
+    pub fn tile(
+        &mut self,
+        entity: &LdtkEntity,
+        maps: &Query<&Handle<LdtkMap>>,
+        ldtk_maps: &Assets<LdtkMap>,
+        texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Option<(Handle<TextureAtlas>, u32)> {
+        let tile = entity.tile.as_ref()?;
+        let ldtk_map =
+            ldtk_maps.get(maps.get(entity.map).ok()?)?;
+        let tileset =
+            ldtk_map.project.defs.tilesets.iter().find(
+                |tileset| tileset.uid == tile.tileset_uid,
+            )?;
+        let texture =
+            ldtk_map.tilesets.get(&tileset.uid)?.clone();
+        let grid_size = tileset.tile_grid_size;
+        let (atlas, columns) = self
+            .atlases
+            .entry(texture.clone())
+            .or_insert_with(|| {
+                let columns = tileset.px_wid / grid_size;
+                let atlas = TextureAtlas::from_grid(
+                    texture,
+                    Vec2::splat(grid_size as f32),
+                    columns as usize,
+                    (tileset.px_hei / grid_size) as usize,
+                );
+                (texture_atlases.add(atlas), columns)
+            });
+        let index = tile.src_rect[1] / grid_size * *columns
+            + tile.src_rect[0] / grid_size;
+        Some((atlas.clone(), index as u32))
+    }
+}
@@ -0,0 +1,300 @@
+use std::fs;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::{
+    na::{Isometry2, Vector2},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    checkpoint::RespawnPoint,
+    parallax::ParallaxBackgroundHandle, spawn_player,
+    state::GameState, tile_behaviour::TileBehaviourTable,
+    Player, PlayerSprites,
+};
+
+/// A level of the game, in the order they are played.
+pub struct LevelInfo {
+    pub name: &'static str,
+    /// Path of the LDtk project for the level.
+    pub map: &'static str,
+    /// Path of the tile behaviour table for the level's
+    /// tileset.
+    pub tile_behaviours: &'static str,
+    /// Path of the level's parallax background.
+    pub background: &'static str,
+}
+
+pub const LEVELS: &[LevelInfo] = &[
+    LevelInfo {
+        name: "Square One",
+        map: "super-corgo-square.ldtk",
+        tile_behaviours:
+            "super-corgo/tiles_packed.tiles.ron",
+        background: "backgrounds/hills.parallax.ron",
+    },
+    LevelInfo {
+        name: "Super Corgo",
+        map: "super-corgo.ldtk",
+        tile_behaviours:
+            "super-corgo/tiles_packed.tiles.ron",
+        background: "backgrounds/hills.parallax.ron",
+    },
+    LevelInfo {
+        name: "Fire",
+        map: "super-corgo-fire.ldtk",
+        tile_behaviours:
+            "super-corgo/tiles_packed_fire.tiles.ron",
+        background: "backgrounds/hills.parallax.ron",
+    },
+    LevelInfo {
+        name: "Fire Two",
+        map: "super-corgo-fire-two.ldtk",
+        tile_behaviours:
+            "super-corgo/tiles_packed_fire.tiles.ron",
+        background: "backgrounds/hills.parallax.ron",
+    },
+];
+
+const SAVE_PATH: &str = "super-corgo-save.ron";
+
+/// Identifier of the LDtk entity level exits are spawned
+/// from.
+const EXIT_IDENTIFIER: &str = "Exit";
+
+/// Which level is being played and how far the player has
+/// got. `unlocked` is saved to disk whenever it changes.
+#[derive(Serialize, Deserialize)]
+pub struct LevelProgress {
+    #[serde(skip)]
+    pub current: usize,
+    /// Number of levels that can be picked in the level
+    /// select.
+    pub unlocked: usize,
+}
+
+impl Default for LevelProgress {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            unlocked: 1,
+        }
+    }
+}
+
+impl LevelProgress {
+    fn load() -> Self {
+        fs::read_to_string(SAVE_PATH)
+            .ok()
+            .and_then(|save| ron::de::from_str(&save).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|save| {
+                fs::write(SAVE_PATH, save)
+                    .map_err(anyhow::Error::from)
+            });
+        if let Err(error) = result {
+            warn!(
+                "failed to save level progress: {}",
+                error
+            );
+        }
+    }
+
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level < self.unlocked.min(LEVELS.len())
+    }
+}
+
+/// Map id of the level's map, the one the systems looking
+/// tiles up through `MapQuery` use.
+const LEVEL_MAP_ID: u16 = 0;
+
+/// Marks the `LdtkWorld` the level is spawned from.
+struct LevelWorld;
+
+/// Marks the exits that finish the level when touched.
+pub struct LevelExit;
+
+/// Sent when the corgi reaches a level exit.
+pub struct LevelCompleted;
+
+fn spawn_level(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    level: &LevelInfo,
+) {
+    commands.insert_resource(TileBehaviourTable(
+        asset_server.load(level.tile_behaviours),
+    ));
+    commands.insert_resource(ParallaxBackgroundHandle(
+        asset_server.load(level.background),
+    ));
+
+    let handle: Handle<LdtkMap> =
+        asset_server.load(level.map);
+
+    // levels are spawned at their position in the LDtk
+    // world, so LDtk world pixels are game pixels
+    commands
+        .spawn_bundle(LdtkWorldBundle {
+            ldtk_map: handle,
+            world: LdtkWorld::new(LEVEL_MAP_ID, [0]),
+            ..Default::default()
+        })
+        .insert(LevelWorld);
+}
+
+/// Spawns the current level and the corgi when play
+/// starts.
+fn setup_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    player_sprites: Res<PlayerSprites>,
+    progress: Res<LevelProgress>,
+    respawn_point: Res<RespawnPoint>,
+) {
+    let level = &LEVELS[progress.current];
+    info!("loading level {}", level.name);
+    spawn_level(&mut commands, &asset_server, level);
+    spawn_player(
+        &mut commands,
+        &player_sprites,
+        respawn_point.0,
+    );
+}
+
+/// Despawns the level, the entities loaded from it and the
+/// corgi when play stops, either to a menu or to restart
+/// the level.
+fn teardown_level(
+    mut commands: Commands,
+    mut map_query: MapQuery,
+    worlds: Query<Entity, With<LevelWorld>>,
+    ldtk_entities: Query<Entity, With<LdtkEntity>>,
+    players: Query<Entity, With<Player>>,
+) {
+    map_query.despawn(&mut commands, LEVEL_MAP_ID);
+    for entity in worlds
+        .iter()
+        .chain(ldtk_entities.iter())
+        .chain(players.iter())
+    {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Gives the exits loaded from the level a sensor collider
+/// the size of the LDtk entity.
+#[allow(clippy::type_complexity)]
+fn setup_exits(
+    mut commands: Commands,
+    rapier_config: Res<RapierConfiguration>,
+    exits: Query<
+        (Entity, &LdtkEntity, &Transform),
+        Added<LevelExit>,
+    >,
+) {
+    for (entity, ldtk_entity, transform) in exits.iter() {
+        let position = transform.translation.truncate()
+            / rapier_config.scale;
+        let half_extents =
+            ldtk_entity.size / 2.0 / rapier_config.scale;
+        commands.entity(entity).insert_bundle(
+            ColliderBundle {
+                shape: ColliderShape::cuboid(
+                    half_extents.x,
+                    half_extents.y,
+                ),
+                collider_type: ColliderType::Sensor,
+                position: ColliderPosition(Isometry2::new(
+                    Vector2::new(position.x, position.y),
+                    0.0,
+                )),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+fn reach_level_exit(
+    narrow_phase: Res<NarrowPhase>,
+    player: Query<Entity, With<Player>>,
+    exits: Query<Entity, With<LevelExit>>,
+    mut level_completed: EventWriter<LevelCompleted>,
+) {
+    let player = match player.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    if exits.iter().any(|exit| {
+        narrow_phase.intersection_pair(
+            player.handle(),
+            exit.handle(),
+        ) == Some(true)
+    }) {
+        level_completed.send(LevelCompleted);
+    }
+}
+
+fn complete_level(
+    mut level_completed: EventReader<LevelCompleted>,
+    mut progress: ResMut<LevelProgress>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if level_completed.iter().next().is_none() {
+        return;
+    }
+    let next = progress.current + 1;
+    if next < LEVELS.len() && progress.unlocked <= next {
+        progress.unlocked = next + 1;
+        progress.save();
+    }
+    // fails only if another transition is already queued
+    // this frame, such as pausing.
+    state.set(GameState::LevelComplete).ok();
+}
+
+pub struct LevelsPlugin;
+
+impl Plugin for LevelsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(LevelProgress::load())
+            .add_event::<LevelCompleted>()
+            .register_ldtk_entity(EXIT_IDENTIFIER, |_| {
+                LevelExit
+            })
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(
+                        setup_level
+                            .system()
+                            .label("setup_level"),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(teardown_level.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(setup_exits.system())
+                    .with_system(
+                        reach_level_exit
+                            .system()
+                            .label("reach_level_exit"),
+                    )
+                    .with_system(
+                        complete_level
+                            .system()
+                            .after("reach_level_exit"),
+                    ),
+            );
+    }
+}
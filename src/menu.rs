@@ -0,0 +1,310 @@
+use bevy::prelude::*;
+
+use crate::{
+    input::Action,
+    levels::{LevelProgress, LEVELS},
+    state::GameState,
+};
+
+pub const FONT: &str = "fonts/DejaVuSans-Bold.ttf";
+
+/// Root node of a menu screen, despawned when the screen's
+/// state is left.
+pub struct MenuScreen;
+
+/// Spawns a full screen overlay with one line of text per
+/// entry in `lines`, the first line being the heading.
+fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    lines: &[String],
+) {
+    let font = asset_server.load(FONT);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(
+                    Val::Percent(100.0),
+                    Val::Percent(100.0),
+                ),
+                flex_direction:
+                    FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(
+                Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ),
+            ..Default::default()
+        })
+        .insert(MenuScreen)
+        .with_children(|parent| {
+            for (i, line) in lines.iter().enumerate() {
+                let font_size =
+                    if i == 0 { 64.0 } else { 28.0 };
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        line.as_str(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+fn despawn_menu(
+    mut commands: Commands,
+    screens: Query<Entity, With<MenuScreen>>,
+) {
+    for screen in screens.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+}
+
+fn setup_title(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    progress: Res<LevelProgress>,
+) {
+    let mut lines = vec!["Super Corgo Run!".to_string()];
+    for (level, info) in LEVELS.iter().enumerate() {
+        if progress.is_unlocked(level) {
+            lines.push(format!(
+                "{}. {}",
+                level + 1,
+                info.name
+            ));
+        } else {
+            lines.push(format!("{}. locked", level + 1));
+        }
+    }
+    lines.push(format!(
+        "Enter to play {}, number keys to pick a level",
+        LEVELS[progress.current].name
+    ));
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        &mut materials,
+        &lines,
+    );
+}
+
+/// Enter plays the current level, number keys pick any
+/// unlocked level.
+fn title_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut actions: ResMut<Input<Action>>,
+    mut progress: ResMut<LevelProgress>,
+    mut state: ResMut<State<GameState>>,
+) {
+    const KEYS: [KeyCode; 9] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+        KeyCode::Key9,
+    ];
+    for (level, key) in KEYS.iter().enumerate() {
+        if keyboard_input.just_pressed(*key)
+            && progress.is_unlocked(level)
+        {
+            progress.current = level;
+            state.set(GameState::Playing).ok();
+            return;
+        }
+    }
+    if actions.just_pressed(Action::Confirm)
+        && state.set(GameState::Playing).is_ok()
+    {
+        actions.reset(Action::Confirm);
+    }
+}
+
+fn setup_paused(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        &mut materials,
+        &[
+            "Paused".to_string(),
+            "Escape to resume".to_string(),
+            "Q to quit to the title screen".to_string(),
+        ],
+    );
+}
+
+fn paused_input(
+    mut actions: ResMut<Input<Action>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if actions.just_pressed(Action::Pause)
+        && state.pop().is_ok()
+    {
+        actions.reset(Action::Pause);
+    } else if actions.just_pressed(Action::Back)
+        && state.replace(GameState::Title).is_ok()
+    {
+        actions.reset(Action::Back);
+    }
+}
+
+fn setup_level_complete(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    progress: Res<LevelProgress>,
+) {
+    let next = match LEVELS.get(progress.current + 1) {
+        Some(next) => {
+            format!("Enter to play {}", next.name)
+        }
+        None => "Enter to return to the title screen"
+            .to_string(),
+    };
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        &mut materials,
+        &[
+            format!(
+                "{} complete!",
+                LEVELS[progress.current].name
+            ),
+            next,
+            "Q to return to the title screen".to_string(),
+        ],
+    );
+}
+
+fn level_complete_input(
+    mut actions: ResMut<Input<Action>>,
+    mut progress: ResMut<LevelProgress>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if actions.just_pressed(Action::Confirm) {
+        let next = if progress.current + 1 < LEVELS.len() {
+            progress.current += 1;
+            GameState::Playing
+        } else {
+            progress.current = 0;
+            GameState::Title
+        };
+        if state.set(next).is_ok() {
+            actions.reset(Action::Confirm);
+        }
+    } else if actions.just_pressed(Action::Back)
+        && state.set(GameState::Title).is_ok()
+    {
+        actions.reset(Action::Back);
+    }
+}
+
+fn setup_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        &mut materials,
+        &[
+            "Game Over".to_string(),
+            "Enter to try again".to_string(),
+            "Q to return to the title screen".to_string(),
+        ],
+    );
+}
+
+fn game_over_input(
+    mut actions: ResMut<Input<Action>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if actions.just_pressed(Action::Confirm)
+        && state.set(GameState::Playing).is_ok()
+    {
+        actions.reset(Action::Confirm);
+    } else if actions.just_pressed(Action::Back)
+        && state.set(GameState::Title).is_ok()
+    {
+        actions.reset(Action::Back);
+    }
+}
+
+/// The title, pause, level complete and game over screens.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Title)
+                .with_system(setup_title.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Title)
+                .with_system(title_input.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Title)
+                .with_system(despawn_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Paused)
+                .with_system(setup_paused.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Paused)
+                .with_system(paused_input.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Paused)
+                .with_system(despawn_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::LevelComplete)
+                .with_system(setup_level_complete.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::LevelComplete)
+                .with_system(level_complete_input.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::LevelComplete)
+                .with_system(despawn_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(setup_game_over.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::GameOver)
+                .with_system(game_over_input.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::GameOver)
+                .with_system(despawn_menu.system()),
+        );
+    }
+}
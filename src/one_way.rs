@@ -0,0 +1,238 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    na::Vector2, prelude::*,
+    rapier::data::ComponentSetOption,
+};
+
+use crate::{
+    controller::{
+        PlayerController, PlayerControllerConfig,
+    },
+    input::Action,
+    platforms::standing_on,
+    state::GameState,
+    Player,
+};
+
+/// Tuning for one-way platforms.
+#[derive(Debug, Clone)]
+pub struct OneWayConfig {
+    /// How long one-way platforms ignore the corgi after it
+    /// drops down through them, in seconds. Long enough to
+    /// fall past the middle of a tile, after which the
+    /// platform no longer catches it anyway.
+    pub drop_through_time: f32,
+}
+
+impl Default for OneWayConfig {
+    fn default() -> Self {
+        Self {
+            drop_through_time: 0.3,
+        }
+    }
+}
+
+/// Marks colliders that can be jumped up through and are
+/// only landed on from above.
+pub struct OneWayPlatform;
+
+/// Added to a body dropping down through one-way
+/// platforms, which don't collide with it until the timer
+/// finishes.
+pub struct DropThrough(Timer);
+
+/// The components the one-way hooks look at, the user data
+/// of `RapierPhysicsPlugin`.
+pub type OneWayUserData<'a> =
+    (Option<&'a OneWayPlatform>, Option<&'a DropThrough>);
+
+/// Contact modification hooks that only keep contacts with
+/// one-way platforms while the other body is above the
+/// platform and not moving up.
+///
+/// Only colliders with `ActiveHooks::MODIFY_SOLVER_CONTACTS`
+/// are passed to the hooks.
+pub struct OneWayHooks {
+    /// Largest angle, in radians, between a contact normal
+    /// and straight up for the contact to count as landing
+    /// on top of the platform.
+    pub allowed_angle: f32,
+    /// Upwards speed above which bodies pass up through
+    /// platforms. Slightly above zero so that a body resting
+    /// on a platform isn't dropped by solver jitter.
+    pub min_rising_speed: f32,
+}
+
+impl Default for OneWayHooks {
+    fn default() -> Self {
+        Self {
+            allowed_angle: FRAC_PI_4,
+            min_rising_speed: 0.1,
+        }
+    }
+}
+
+impl OneWayHooks {
+    /// Whether a body moving at `vertical_speed` goes
+    /// through platforms rather than landing on them.
+    fn passes_through(
+        &self,
+        dropping: bool,
+        vertical_speed: f32,
+    ) -> bool {
+        dropping || vertical_speed > self.min_rising_speed
+    }
+}
+
+impl<'a> PhysicsHooksWithQuery<OneWayUserData<'a>>
+    for OneWayHooks
+{
+    fn modify_solver_contacts(
+        &self,
+        context: &mut ContactModificationContext<
+            RigidBodyComponentsSet,
+            ColliderComponentsSet,
+        >,
+        user_data: &Query<OneWayUserData<'a>>,
+    ) {
+        let is_platform = |collider: ColliderHandle| {
+            matches!(
+                user_data.get(collider.entity()),
+                Ok((Some(_), _))
+            )
+        };
+        // the normal that may be kept is given in
+        // collider1's local space, pointing out of it
+        let (allowed_normal, other, other_body) =
+            if is_platform(context.collider1) {
+                (
+                    Vector2::y(),
+                    context.collider2,
+                    context.rigid_body2,
+                )
+            } else if is_platform(context.collider2) {
+                (
+                    -Vector2::y(),
+                    context.collider1,
+                    context.rigid_body1,
+                )
+            } else {
+                return;
+            };
+
+        let dropping = matches!(
+            user_data.get(other.entity()),
+            Ok((_, Some(_)))
+        );
+        let vertical_speed = other_body
+            .and_then(|body| {
+                ComponentSetOption::<RigidBodyVelocity>::get(
+                    context.bodies,
+                    body.0,
+                )
+            })
+            .map_or(0.0, |velocity| velocity.linvel.y);
+        if self.passes_through(dropping, vertical_speed) {
+            context.solver_contacts.clear();
+            return;
+        }
+        context.update_as_oneway_platform(
+            &allowed_normal,
+            self.allowed_angle,
+        );
+    }
+}
+
+/// Holding down while pressing jump drops the corgi through
+/// the one-way platform it is standing on. The controller
+/// reads `on_one_way_platform` to skip the jump.
+fn drop_through(
+    mut commands: Commands,
+    actions: Res<Input<Action>>,
+    narrow_phase: Res<NarrowPhase>,
+    config: Res<OneWayConfig>,
+    controller_config: Res<PlayerControllerConfig>,
+    mut players: Query<
+        (Entity, &mut PlayerController),
+        With<Player>,
+    >,
+    platforms: Query<Entity, With<OneWayPlatform>>,
+) {
+    for (player, mut controller) in players.iter_mut() {
+        controller.on_one_way_platform =
+            platforms.iter().any(|platform| {
+                standing_on(
+                    &narrow_phase,
+                    player,
+                    platform,
+                    controller_config.min_ground_normal,
+                )
+            });
+        if controller.on_one_way_platform
+            && actions.pressed(Action::MoveDown)
+            && actions.just_pressed(Action::Jump)
+        {
+            commands.entity(player).insert(DropThrough(
+                Timer::from_seconds(
+                    config.drop_through_time,
+                    false,
+                ),
+            ));
+        }
+    }
+}
+
+fn finish_drop_through(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dropping: Query<(Entity, &mut DropThrough)>,
+) {
+    for (entity, mut drop_through) in dropping.iter_mut() {
+        if drop_through.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<DropThrough>();
+        }
+    }
+}
+
+pub struct OneWayPlugin;
+
+impl Plugin for OneWayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<OneWayConfig>()
+            .insert_resource(PhysicsHooksWithQueryObject::<
+                OneWayUserData,
+            >(Box::new(
+                OneWayHooks::default(),
+            )))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        drop_through
+                            .system()
+                            .before("jump"),
+                    )
+                    .with_system(
+                        finish_drop_through.system(),
+                    ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lands_unless_rising_or_dropping() {
+        let hooks = OneWayHooks::default();
+        assert!(!hooks.passes_through(false, -5.0));
+        // resting on the platform, with solver jitter
+        assert!(!hooks.passes_through(false, 0.0));
+        assert!(!hooks.passes_through(false, 0.05));
+        assert!(hooks.passes_through(false, 5.0));
+        assert!(hooks.passes_through(true, -5.0));
+        assert!(hooks.passes_through(true, 0.0));
+    }
+}
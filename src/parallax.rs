@@ -0,0 +1,255 @@
+use bevy::{
+    asset::{
+        AssetLoader, BoxedFuture, LoadContext, LoadedAsset,
+    },
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::Deserialize;
+
+use crate::{state::GameState, MainCamera};
+
+/// Background layers sit behind the tile layers, which start
+/// at z = 0.
+const BACKGROUND_Z: f32 = -100.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParallaxLayerConfig {
+    /// Asset path of the layer's image.
+    pub image: String,
+    /// Size of the image in world pixels.
+    pub size: Vec2,
+    /// Where the layer's center sits when the camera is at
+    /// the world origin.
+    #[serde(default)]
+    pub offset: Vec2,
+    /// How far the layer moves with the camera, 0.0 is fixed
+    /// to the screen and 1.0 moves with the level.
+    pub scroll: Vec2,
+    /// Repeat the image to fill the view horizontally.
+    #[serde(default)]
+    pub repeat_x: bool,
+}
+
+/// A set of parallax layers, loaded from `*.parallax.ron`
+/// files. Layers are drawn back to front.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "4d0c7b2a-93e1-4f6d-a5b8-1e2f3c4d5a6b"]
+pub struct ParallaxBackground {
+    pub layers: Vec<ParallaxLayerConfig>,
+}
+
+/// The background used by the current level.
+pub struct ParallaxBackgroundHandle(
+    pub Handle<ParallaxBackground>,
+);
+
+#[derive(Default)]
+pub struct ParallaxBackgroundLoader;
+
+impl AssetLoader for ParallaxBackgroundLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let background: ParallaxBackground =
+                ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(
+                LoadedAsset::new(background),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["parallax.ron"];
+        EXTENSIONS
+    }
+}
+
+/// A background layer, the parent of one sprite or of a row
+/// of repeated sprites.
+pub struct ParallaxLayer {
+    offset: Vec2,
+    scroll: Vec2,
+    width: f32,
+    repeat_x: bool,
+    /// Width of the view the copies were spawned for.
+    view_width: f32,
+}
+
+fn half_view(
+    windows: &Windows,
+    camera: &Transform,
+) -> Vec2 {
+    windows.get_primary().map_or(Vec2::ZERO, |window| {
+        Vec2::new(window.width(), window.height()) / 2.0
+    }) * camera.scale.truncate()
+}
+
+fn view_width(
+    windows: &Windows,
+    camera: &Query<&Transform, With<MainCamera>>,
+) -> f32 {
+    camera.single().map_or(0.0, |camera| {
+        half_view(windows, camera).x * 2.0
+    })
+}
+
+/// Spawns the level's background once it has loaded.
+#[allow(clippy::too_many_arguments)]
+fn spawn_parallax(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    windows: Res<Windows>,
+    background: Option<Res<ParallaxBackgroundHandle>>,
+    backgrounds: Res<Assets<ParallaxBackground>>,
+    layers: Query<(), With<ParallaxLayer>>,
+    camera: Query<&Transform, With<MainCamera>>,
+) {
+    if layers.iter().next().is_some() {
+        return;
+    }
+    let background = match background
+        .and_then(|handle| backgrounds.get(&handle.0))
+    {
+        Some(background) => background,
+        None => return,
+    };
+    let view_width = view_width(&windows, &camera);
+
+    for (i, config) in background.layers.iter().enumerate()
+    {
+        let material =
+            materials.add(ColorMaterial::texture(
+                asset_server.load(config.image.as_str()),
+            ));
+        // enough copies to cover the view wherever the row
+        // is snapped to
+        let copies = if config.repeat_x {
+            (view_width / config.size.x).ceil() as usize + 1
+        } else {
+            1
+        };
+        commands
+            .spawn_bundle((
+                Transform::from_xyz(
+                    config.offset.x,
+                    config.offset.y,
+                    BACKGROUND_Z + i as f32,
+                ),
+                GlobalTransform::identity(),
+                ParallaxLayer {
+                    offset: config.offset,
+                    scroll: config.scroll,
+                    width: config.size.x,
+                    repeat_x: config.repeat_x,
+                    view_width,
+                },
+            ))
+            .with_children(|parent| {
+                for copy in 0..copies {
+                    parent.spawn_bundle(SpriteBundle {
+                        material: material.clone(),
+                        sprite: Sprite::new(config.size),
+                        transform: Transform::from_xyz(
+                            copy as f32 * config.size.x,
+                            0.0,
+                            0.0,
+                        ),
+                        ..Default::default()
+                    });
+                }
+            });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn scroll_parallax(
+    windows: Res<Windows>,
+    camera: Query<
+        &Transform,
+        (With<MainCamera>, Without<ParallaxLayer>),
+    >,
+    mut layers: Query<(&ParallaxLayer, &mut Transform)>,
+) {
+    let camera = match camera.single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let camera_position = camera.translation.truncate();
+    let left =
+        camera_position.x - half_view(&windows, camera).x;
+
+    for (layer, mut transform) in layers.iter_mut() {
+        let position = camera_position
+            * (Vec2::ONE - layer.scroll)
+            + layer.offset;
+        transform.translation.y = position.y;
+        transform.translation.x = if layer.repeat_x {
+            // snap the row to the copy covering the left
+            // edge of the view
+            let copy = ((left - position.x) / layer.width
+                + 0.5)
+                .floor();
+            position.x + copy * layer.width
+        } else {
+            position.x
+        };
+    }
+}
+
+/// Despawns the background once the view is wider or
+/// narrower than its repeated layers were spawned for, such
+/// as after resizing the window, so that `spawn_parallax`
+/// spawns it again with the right number of copies.
+fn resize_parallax(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    camera: Query<&Transform, With<MainCamera>>,
+    layers: Query<(Entity, &ParallaxLayer)>,
+) {
+    let view_width = view_width(&windows, &camera);
+    if layers.iter().any(|(_, layer)| {
+        layer.repeat_x && layer.view_width != view_width
+    }) {
+        for (layer, _) in layers.iter() {
+            commands.entity(layer).despawn_recursive();
+        }
+    }
+}
+
+fn despawn_parallax(
+    mut commands: Commands,
+    layers: Query<Entity, With<ParallaxLayer>>,
+) {
+    for layer in layers.iter() {
+        commands.entity(layer).despawn_recursive();
+    }
+}
+
+pub struct ParallaxPlugin;
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<ParallaxBackground>()
+            .init_asset_loader::<ParallaxBackgroundLoader>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(spawn_parallax.system())
+                    .with_system(resize_parallax.system())
+                    .with_system(
+                        scroll_parallax
+                            .system()
+                            .after("follow_player"),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(despawn_parallax.system()),
+            );
+    }
+}
@@ -0,0 +1,730 @@
+use std::{f32::consts::TAU, iter};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::{na::Isometry2, prelude::*};
+
+use crate::{
+    controller::{
+        PlayerController, PlayerControllerConfig,
+    },
+    coords::TileCoords,
+    ldtk_entities::EntitySprites,
+    state::GameState,
+    Player,
+};
+
+/// Identifier of the LDtk entity moving platforms are
+/// spawned from. Its `path` field lists the cells the
+/// platform visits after its own and `speed` is optional.
+const MOVING_PLATFORM_IDENTIFIER: &str = "MovingPlatform";
+
+/// Tuning for moving and crumbling platforms. Speeds are in
+/// physics units per second and times in seconds.
+#[derive(Debug, Clone)]
+pub struct PlatformConfig {
+    /// Speed of moving platforms that don't set their own.
+    pub platform_speed: f32,
+    /// How long a crumbling tile shakes before it falls.
+    pub crumble_time: f32,
+    /// How long a crumbled tile takes to come back.
+    pub crumble_respawn_time: f32,
+    /// How far a crumbling tile shakes, in pixels.
+    pub shake_distance: f32,
+    /// Shakes per second.
+    pub shake_frequency: f32,
+}
+
+impl Default for PlatformConfig {
+    fn default() -> Self {
+        Self {
+            platform_speed: 2.0,
+            crumble_time: 0.5,
+            crumble_respawn_time: 3.0,
+            shake_distance: 1.0,
+            shake_frequency: 20.0,
+        }
+    }
+}
+
+/// A kinematic platform going back and forth along a path.
+pub struct MovingPlatform {
+    /// Positions of the platform's center, in physics
+    /// units. The first is where the platform starts.
+    path: Vec<Vec2>,
+    speed: f32,
+    /// Index of the point the platform is heading to.
+    target: usize,
+    /// Heading back to the start of the path.
+    reverse: bool,
+    /// Velocity over the last step, used to carry the corgi.
+    velocity: Vec2,
+}
+
+impl MovingPlatform {
+    fn new(path: Vec<Vec2>, speed: f32) -> Self {
+        Self {
+            path,
+            speed,
+            target: 1,
+            reverse: false,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    fn advance(&mut self) {
+        let last = self.path.len() - 1;
+        if self.reverse {
+            if self.target == 0 {
+                self.reverse = false;
+                self.target = 1;
+            } else {
+                self.target -= 1;
+            }
+        } else if self.target == last {
+            self.reverse = true;
+            self.target = last - 1;
+        } else {
+            self.target += 1;
+        }
+    }
+
+    /// Moves `distance` along the path from `position`,
+    /// turning back at either end.
+    fn step(
+        &mut self,
+        mut position: Vec2,
+        mut distance: f32,
+    ) -> Vec2 {
+        if self.path.len() < 2 {
+            return position;
+        }
+        // bounded so that a path of points on top of each
+        // other can't loop forever
+        for _ in 0..self.path.len() * 2 {
+            let to_target =
+                self.path[self.target] - position;
+            let length = to_target.length();
+            if length > distance {
+                return position
+                    + to_target * (distance / length);
+            }
+            position = self.path[self.target];
+            distance -= length;
+            self.advance();
+        }
+        position
+    }
+}
+
+/// The path of a moving platform as placed in LDtk, added
+/// to the loaded entity and replaced by a `MovingPlatform`
+/// once the platform is set up.
+struct PlatformPath {
+    /// Offsets of the points after the first from the
+    /// platform's center, in world pixels.
+    offsets: Vec<Vec2>,
+    speed: Option<f32>,
+}
+
+impl PlatformPath {
+    fn from_ldtk(entity: &LdtkEntity) -> Self {
+        Self {
+            offsets: entity
+                .field_points("path")
+                .into_iter()
+                .map(|cell| entity.cell_offset(cell))
+                .collect(),
+            speed: entity.field_f32("speed"),
+        }
+    }
+}
+
+/// Marks tiles that crumble after the corgi stands on them.
+pub struct CrumblingTile;
+
+/// A crumbling tile the corgi has stood on.
+struct Crumbling {
+    timer: Timer,
+    /// The sprite shaking in place of the tile.
+    shake: Entity,
+}
+
+/// A sprite standing in for a crumbling tile while it
+/// shakes, as tiles can't be moved on their own.
+struct CrumbleShake {
+    origin: Vec2,
+}
+
+/// A crumbled tile waiting to come back.
+struct CrumbledTile {
+    tile_pos: UVec2,
+    texture_index: u16,
+    timer: Timer,
+}
+
+/// Whether `entity` has an active contact with `ground`
+/// whose normal points up, out of `ground`.
+pub fn standing_on(
+    narrow_phase: &NarrowPhase,
+    entity: Entity,
+    ground: Entity,
+    min_normal: f32,
+) -> bool {
+    let handle = entity.handle();
+    narrow_phase
+        .contact_pair(handle, ground.handle())
+        .map_or(false, |contact_pair| {
+            contact_pair.has_any_active_contact
+                && contact_pair.manifolds.iter().any(
+                    |manifold| {
+                        // the normal points from collider1 to
+                        // collider2
+                        let up = if contact_pair.collider1
+                            == handle
+                        {
+                            -manifold.data.normal.y
+                        } else {
+                            manifold.data.normal.y
+                        };
+                        !manifold
+                            .data
+                            .solver_contacts
+                            .is_empty()
+                            && up >= min_normal
+                    },
+                )
+        })
+}
+
+/// Turns the moving platforms loaded from the level into
+/// kinematic bodies showing the entity's tile across their
+/// width.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+fn setup_platforms(
+    mut commands: Commands,
+    config: Res<PlatformConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    ldtk_maps: Res<Assets<LdtkMap>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut sprites: Local<EntitySprites>,
+    maps: Query<&Handle<LdtkMap>>,
+    platforms: Query<
+        (Entity, &LdtkEntity, &Transform, &PlatformPath),
+        Added<PlatformPath>,
+    >,
+) {
+    for (entity, ldtk_entity, transform, platform_path) in
+        platforms.iter()
+    {
+        let center = transform.translation.truncate();
+        let path = iter::once(center)
+            .chain(
+                platform_path
+                    .offsets
+                    .iter()
+                    .map(|offset| center + *offset),
+            )
+            .map(|world| world / rapier_config.scale)
+            .collect::<Vec<_>>();
+        let speed = platform_path
+            .speed
+            .unwrap_or(config.platform_speed);
+        let size = ldtk_entity.size;
+        let half_extents = size / 2.0 / rapier_config.scale;
+
+        let mut platform = commands.entity(entity);
+        platform
+            .insert_bundle(RigidBodyBundle {
+                body_type:
+                    RigidBodyType::KinematicPositionBased,
+                position: path[0].into(),
+                ..Default::default()
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(
+                    half_extents.x,
+                    half_extents.y,
+                ),
+                material: ColliderMaterial {
+                    restitution: 0.0,
+                    friction: 0.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Transform::from_translation(
+                center.extend(1.0),
+            ))
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(MovingPlatform::new(path, speed))
+            .remove::<PlatformPath>();
+
+        // the entity's tile repeated across its width
+        if let (Some((texture_atlas, index)), Some(tile)) = (
+            sprites.tile(
+                ldtk_entity,
+                &maps,
+                &ldtk_maps,
+                &mut texture_atlases,
+            ),
+            ldtk_entity.tile,
+        ) {
+            let tile_width = tile.src_rect[2] as f32;
+            let count =
+                (size.x / tile_width).ceil() as usize;
+            platform.with_children(|parent| {
+                for i in 0..count {
+                    parent.spawn_bundle(
+                        SpriteSheetBundle {
+                            texture_atlas: texture_atlas
+                                .clone(),
+                            sprite: TextureAtlasSprite::new(
+                                index,
+                            ),
+                            transform: Transform::from_xyz(
+                                (i as f32 + 0.5)
+                                    * tile_width
+                                    - size.x / 2.0,
+                                0.0,
+                                0.0,
+                            ),
+                            ..Default::default()
+                        },
+                    );
+                }
+            });
+        }
+    }
+}
+
+fn move_platforms(
+    integration_parameters: Res<IntegrationParameters>,
+    mut platforms: Query<(
+        &mut MovingPlatform,
+        &mut RigidBodyPosition,
+    )>,
+) {
+    // physics takes one step of `dt` per frame
+    let dt = integration_parameters.dt;
+    for (mut platform, mut position) in platforms.iter_mut()
+    {
+        let translation = position.position.translation;
+        let current =
+            Vec2::new(translation.x, translation.y);
+        let speed = platform.speed;
+        let next = platform.step(current, speed * dt);
+        platform.velocity = (next - current) / dt;
+        position.next_position =
+            Isometry2::translation(next.x, next.y);
+    }
+}
+
+/// Lets the corgi ride moving platforms by running relative
+/// to the platform it is standing on.
+fn carry_player(
+    narrow_phase: Res<NarrowPhase>,
+    controller_config: Res<PlayerControllerConfig>,
+    platforms: Query<(Entity, &MovingPlatform)>,
+    mut players: Query<
+        (Entity, &mut PlayerController),
+        With<Player>,
+    >,
+) {
+    for (player, mut controller) in players.iter_mut() {
+        controller.ground_velocity = platforms
+            .iter()
+            .find(|(platform, _)| {
+                standing_on(
+                    &narrow_phase,
+                    player,
+                    *platform,
+                    controller_config.min_ground_normal,
+                )
+            })
+            .map_or(Vec2::ZERO, |(_, platform)| {
+                platform.velocity
+            });
+    }
+}
+
+/// Starts crumbling tiles the corgi stands on, swapping the
+/// tile for a sprite that shakes.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+fn start_crumbling(
+    mut commands: Commands,
+    narrow_phase: Res<NarrowPhase>,
+    config: Res<PlatformConfig>,
+    controller_config: Res<PlayerControllerConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    materials: Res<Assets<ColorMaterial>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    player: Query<Entity, With<Player>>,
+    mut tiles: Query<
+        (Entity, &mut Tile, &UVec2, &TileParent),
+        (With<CrumblingTile>, Without<Crumbling>),
+    >,
+    chunk_materials: Query<&Handle<ColorMaterial>>,
+    transforms: Query<(&Transform, Option<&Parent>)>,
+    mut map_query: MapQuery,
+    mut tileset_atlas: Local<
+        Option<(Handle<Texture>, Handle<TextureAtlas>)>,
+    >,
+) {
+    let player = match player.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let (coords, settings) = match map_query
+        .get_layer(0u16, 0u16)
+        .and_then(|(layer_entity, layer)| {
+            Some((
+                TileCoords::for_layer(
+                    layer_entity,
+                    layer,
+                    &transforms,
+                    rapier_config.scale,
+                )?,
+                layer.settings,
+            ))
+        }) {
+        Some(layer) => layer,
+        None => return,
+    };
+
+    for (entity, mut tile, tile_pos, tile_parent) in
+        tiles.iter_mut()
+    {
+        if !standing_on(
+            &narrow_phase,
+            player,
+            entity,
+            controller_config.min_ground_normal,
+        ) {
+            continue;
+        }
+        let texture = match chunk_materials
+            .get(tile_parent.chunk)
+            .ok()
+            .and_then(|material| materials.get(material))
+            .and_then(|material| material.texture.clone())
+        {
+            Some(texture) => texture,
+            None => continue,
+        };
+        // levels share tilesets, keep the atlas until the
+        // texture changes
+        let texture_atlas = match &*tileset_atlas {
+            Some((atlas_texture, texture_atlas))
+                if *atlas_texture == texture =>
+            {
+                texture_atlas.clone()
+            }
+            _ => {
+                let texture_atlas = texture_atlases.add(
+                    TextureAtlas::from_grid(
+                        texture.clone(),
+                        settings.tile_size,
+                        (settings.texture_size.x
+                            / settings.tile_size.x)
+                            as usize,
+                        (settings.texture_size.y
+                            / settings.tile_size.y)
+                            as usize,
+                    ),
+                );
+                *tileset_atlas =
+                    Some((texture, texture_atlas.clone()));
+                texture_atlas
+            }
+        };
+
+        let origin = coords.tile_to_world(*tile_pos);
+        let shake = commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas,
+                sprite: TextureAtlasSprite::new(
+                    tile.texture_index as u32,
+                ),
+                // just above the main level layer
+                transform: Transform::from_xyz(
+                    origin.x, origin.y, 0.5,
+                ),
+                ..Default::default()
+            })
+            .insert(CrumbleShake { origin })
+            .id();
+        tile.visible = false;
+        map_query
+            .notify_chunk_for_tile(*tile_pos, 0u16, 0u16);
+        commands.entity(entity).insert(Crumbling {
+            timer: Timer::from_seconds(
+                config.crumble_time,
+                false,
+            ),
+            shake,
+        });
+    }
+}
+
+/// Shakes crumbling tiles and removes them once their time
+/// is up.
+fn crumble(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<PlatformConfig>,
+    mut crumbling: Query<(&UVec2, &Tile, &mut Crumbling)>,
+    mut shakes: Query<(&CrumbleShake, &mut Transform)>,
+    mut map_query: MapQuery,
+) {
+    for (tile_pos, tile, mut crumbling) in
+        crumbling.iter_mut()
+    {
+        crumbling.timer.tick(time.delta());
+        if let Ok((shake, mut transform)) =
+            shakes.get_mut(crumbling.shake)
+        {
+            let phase = crumbling.timer.elapsed_secs()
+                * config.shake_frequency
+                * TAU;
+            transform.translation.x = shake.origin.x
+                + phase.sin() * config.shake_distance;
+        }
+        if !crumbling.timer.finished() {
+            continue;
+        }
+
+        commands.entity(crumbling.shake).despawn();
+        commands.spawn().insert(CrumbledTile {
+            tile_pos: *tile_pos,
+            texture_index: tile.texture_index,
+            timer: Timer::from_seconds(
+                config.crumble_respawn_time,
+                false,
+            ),
+        });
+        // despawning the tile takes its collider with it
+        if map_query
+            .despawn_tile(
+                &mut commands,
+                *tile_pos,
+                0u16,
+                0u16,
+            )
+            .is_ok()
+        {
+            map_query.notify_chunk_for_tile(
+                *tile_pos, 0u16, 0u16,
+            );
+        }
+    }
+}
+
+/// Puts crumbled tiles back once their time is up and the
+/// corgi is out of the way.
+fn respawn_crumbled_tiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    player: Query<&RigidBodyPosition, With<Player>>,
+    mut crumbled: Query<(Entity, &mut CrumbledTile)>,
+    transforms: Query<(&Transform, Option<&Parent>)>,
+    mut map_query: MapQuery,
+) {
+    let coords = match map_query
+        .get_layer(0u16, 0u16)
+        .and_then(|(layer_entity, layer)| {
+            TileCoords::for_layer(
+                layer_entity,
+                layer,
+                &transforms,
+                rapier_config.scale,
+            )
+        }) {
+        Some(coords) => coords,
+        None => return,
+    };
+    let player = player.single().ok().map(|position| {
+        let translation = position.position.translation;
+        Vec2::new(translation.x, translation.y)
+    });
+
+    for (entity, mut crumbled) in crumbled.iter_mut() {
+        if !crumbled.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        // a tile's size apart is a little more than half the
+        // corgi and half the tile, close enough to not trap
+        // the corgi inside the tile
+        let reach =
+            coords.pixels_to_physics(coords.tile_size);
+        let blocked = player.map_or(false, |player| {
+            let offset = (player
+                - coords
+                    .tile_to_physics(crumbled.tile_pos))
+            .abs();
+            offset.x < reach.x && offset.y < reach.y
+        });
+        if blocked {
+            continue;
+        }
+
+        if map_query
+            .set_tile(
+                &mut commands,
+                crumbled.tile_pos,
+                Tile {
+                    texture_index: crumbled.texture_index,
+                    ..Default::default()
+                },
+                0u16,
+                0u16,
+            )
+            .is_ok()
+        {
+            map_query.notify_chunk_for_tile(
+                crumbled.tile_pos,
+                0u16,
+                0u16,
+            );
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_platforms(
+    mut commands: Commands,
+    platforms: Query<
+        Entity,
+        Or<(With<CrumbleShake>, With<CrumbledTile>)>,
+    >,
+) {
+    for platform in platforms.iter() {
+        commands.entity(platform).despawn_recursive();
+    }
+}
+
+pub struct PlatformPlugin;
+
+impl Plugin for PlatformPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PlatformConfig>()
+            .register_ldtk_entity(
+                MOVING_PLATFORM_IDENTIFIER,
+                PlatformPath::from_ldtk,
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(setup_platforms.system())
+                    .with_system(
+                        move_platforms
+                            .system()
+                            .label("move_platforms"),
+                    )
+                    .with_system(
+                        carry_player
+                            .system()
+                            .after("detect_ground")
+                            .after("move_platforms")
+                            .before("run"),
+                    )
+                    .with_system(start_crumbling.system())
+                    .with_system(crumble.system())
+                    .with_system(
+                        respawn_crumbled_tiles.system(),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(
+                        despawn_platforms.system(),
+                    ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on_path(points: &[(f32, f32)]) -> MovingPlatform {
+        MovingPlatform::new(
+            points
+                .iter()
+                .map(|(x, y)| Vec2::new(*x, *y))
+                .collect(),
+            1.0,
+        )
+    }
+
+    #[test]
+    fn turns_back_at_the_last_point() {
+        let mut platform =
+            on_path(&[(0.0, 0.0), (10.0, 0.0)]);
+        let position =
+            platform.step(Vec2::new(8.0, 0.0), 5.0);
+        assert_eq!(position, Vec2::new(7.0, 0.0));
+        assert!(platform.reverse);
+        assert_eq!(platform.target, 0);
+    }
+
+    #[test]
+    fn turns_back_at_the_first_point() {
+        let mut platform = on_path(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+        ]);
+        platform.reverse = true;
+        platform.target = 0;
+        let position =
+            platform.step(Vec2::new(2.0, 0.0), 5.0);
+        assert_eq!(position, Vec2::new(3.0, 0.0));
+        assert!(!platform.reverse);
+        assert_eq!(platform.target, 1);
+    }
+
+    #[test]
+    fn crosses_several_segments_in_one_step() {
+        let mut platform = on_path(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+        ]);
+        let position = platform.step(Vec2::ZERO, 15.0);
+        assert_eq!(position, Vec2::new(10.0, 5.0));
+        assert_eq!(platform.target, 2);
+
+        // there and back again, and on
+        let mut platform =
+            on_path(&[(0.0, 0.0), (4.0, 0.0)]);
+        let position = platform.step(Vec2::ZERO, 10.0);
+        assert_eq!(position, Vec2::new(2.0, 0.0));
+        assert!(!platform.reverse);
+        assert_eq!(platform.target, 1);
+    }
+
+    #[test]
+    fn coincident_points_do_not_loop_forever() {
+        let point = (5.0, 5.0);
+        let mut platform = on_path(&[point, point, point]);
+        let position =
+            platform.step(Vec2::new(5.0, 5.0), 1.0);
+        assert_eq!(position, Vec2::new(5.0, 5.0));
+    }
+
+    #[test]
+    fn single_point_path_stays_put() {
+        let mut platform = on_path(&[(0.0, 0.0)]);
+        let position =
+            platform.step(Vec2::new(3.0, 4.0), 10.0);
+        assert_eq!(position, Vec2::new(3.0, 4.0));
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::{state::GameState, PlayerDied};
+
+const STARTING_LIVES: u32 = 3;
+
+/// Coins collected this run.
+#[derive(Debug, Default)]
+pub struct Score(pub u32);
+
+/// Lives left this run, the game is over when they run out.
+#[derive(Debug)]
+pub struct Lives(pub u32);
+
+impl Default for Lives {
+    fn default() -> Self {
+        Self(STARTING_LIVES)
+    }
+}
+
+/// Time spent playing the current level, not counting time
+/// spent paused.
+#[derive(Debug, Default)]
+pub struct LevelTimer(pub f32);
+
+/// Sent when the corgi picks up a coin.
+pub struct CoinCollected {
+    /// World position of the coin.
+    pub position: Vec2,
+}
+
+fn collect_coins(
+    mut coin_collected: EventReader<CoinCollected>,
+    mut score: ResMut<Score>,
+) {
+    score.0 += coin_collected.iter().count() as u32;
+}
+
+/// Takes a life for every death, ending the game when they
+/// run out.
+fn lose_life(
+    mut player_died: EventReader<PlayerDied>,
+    mut lives: ResMut<Lives>,
+    mut state: ResMut<State<GameState>>,
+) {
+    // dying to several things at once only costs one life
+    if let Some(died) = player_died.iter().last() {
+        info!("corgi died: {:?}", died.cause);
+        lives.0 = lives.0.saturating_sub(1);
+    }
+    if lives.0 == 0 {
+        // leaving `Playing` despawns the corgi
+        state.set(GameState::GameOver).ok();
+    }
+}
+
+fn tick_level_timer(
+    time: Res<Time>,
+    mut level_timer: ResMut<LevelTimer>,
+) {
+    level_timer.0 += time.delta_seconds();
+}
+
+fn reset_level_timer(mut level_timer: ResMut<LevelTimer>) {
+    level_timer.0 = 0.0;
+}
+
+/// Starts a new run when leaving the title or game over
+/// screens.
+fn new_run(
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+) {
+    *score = Score::default();
+    *lives = Lives::default();
+}
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Score>()
+            .init_resource::<Lives>()
+            .init_resource::<LevelTimer>()
+            .add_event::<CoinCollected>()
+            .add_system_set(
+                SystemSet::on_exit(GameState::Title)
+                    .with_system(new_run.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver)
+                    .with_system(new_run.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(
+                        reset_level_timer.system(),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(collect_coins.system())
+                    .with_system(
+                        lose_life
+                            .system()
+                            .label("lose_life")
+                            .after("check_death"),
+                    )
+                    .with_system(tick_level_timer.system()),
+            );
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    coords::TileCoords,
+    ladder::ladder_layer_id,
+    state::GameState,
+    tile_behaviour::{
+        TileBehaviour, TileBehaviourTable, TileBehaviours,
+    },
+    Player,
+};
+
+/// Tuning for secret areas.
+#[derive(Debug, Clone)]
+pub struct SecretConfig {
+    /// How long a secret area takes to fade out or back in,
+    /// in seconds.
+    pub fade_time: f32,
+}
+
+impl Default for SecretConfig {
+    fn default() -> Self {
+        Self { fade_time: 0.3 }
+    }
+}
+
+/// The tiles of the secret area the corgi is inside, which
+/// are faded out.
+#[derive(Default)]
+pub struct RevealedSecret(Option<HashSet<UVec2>>);
+
+/// Fades a secret overlay tile's alpha towards `target`.
+struct SecretFade {
+    target: f32,
+}
+
+/// Every tile connected to `start` through the sides of
+/// tiles for which `is_secret` holds.
+fn flood_fill(
+    start: UVec2,
+    is_secret: impl Fn(UVec2) -> bool,
+) -> HashSet<UVec2> {
+    let mut region = HashSet::new();
+    let mut queue = VecDeque::new();
+    region.insert(start);
+    queue.push_back(start);
+    while let Some(tile_pos) = queue.pop_front() {
+        let neighbors = [
+            tile_pos
+                .x
+                .checked_sub(1)
+                .map(|x| UVec2::new(x, tile_pos.y)),
+            Some(tile_pos + UVec2::X),
+            tile_pos
+                .y
+                .checked_sub(1)
+                .map(|y| UVec2::new(tile_pos.x, y)),
+            Some(tile_pos + UVec2::Y),
+        ];
+        for neighbor in neighbors.iter().flatten() {
+            if !region.contains(neighbor)
+                && is_secret(*neighbor)
+            {
+                region.insert(*neighbor);
+                queue.push_back(*neighbor);
+            }
+        }
+    }
+    region
+}
+
+/// Fades out the connected region of overlay tiles the
+/// corgi walks into, and fades it back in once the corgi
+/// leaves it.
+///
+/// Every tile on the secrets layer that isn't a ladder is
+/// part of the overlay.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+fn reveal_secrets(
+    mut commands: Commands,
+    mut revealed: ResMut<RevealedSecret>,
+    rapier_config: Res<RapierConfiguration>,
+    tile_behaviour_table: Res<TileBehaviourTable>,
+    tile_behaviours: Res<Assets<TileBehaviours>>,
+    player: Query<&RigidBodyPosition, With<Player>>,
+    transforms: Query<(&Transform, Option<&Parent>)>,
+    tiles: Query<&Tile>,
+    ldtk_layers: Query<&LdtkLayer>,
+    map_query: MapQuery,
+) {
+    let tile_behaviours = match tile_behaviours
+        .get(&tile_behaviour_table.0)
+    {
+        Some(tile_behaviours) => tile_behaviours,
+        None => return,
+    };
+    let position = match player.single() {
+        Ok(position) => position,
+        Err(_) => return,
+    };
+    let layer_id = match ladder_layer_id(&ldtk_layers) {
+        Some(layer_id) => layer_id,
+        None => return,
+    };
+    let (layer_entity, layer) =
+        match map_query.get_layer(0u16, layer_id) {
+            Some(layer) => layer,
+            None => return,
+        };
+    let coords = match TileCoords::for_layer(
+        layer_entity,
+        layer,
+        &transforms,
+        rapier_config.scale,
+    ) {
+        Some(coords) => coords,
+        None => return,
+    };
+    // fade back in to the opacity set in LDtk
+    let opacity = ldtk_layers
+        .get(layer_entity)
+        .map_or(1.0, |ldtk_layer| ldtk_layer.opacity);
+    let tile_entity = |tile_pos: UVec2| {
+        map_query
+            .get_tile_entity(tile_pos, 0u16, layer_id)
+            .ok()
+    };
+    let is_secret = |tile_pos: UVec2| {
+        tile_entity(tile_pos)
+            .and_then(|tile_entity| {
+                tiles.get(tile_entity).ok()
+            })
+            .map_or(false, |tile| {
+                tile_behaviours
+                    .get(tile.texture_index)
+                    .map_or(true, |definition| {
+                        definition.behaviour
+                            != TileBehaviour::Ladder
+                    })
+            })
+    };
+    let mut fade = |tile_pos: &UVec2, target: f32| {
+        if let Some(tile_entity) = tile_entity(*tile_pos) {
+            commands
+                .entity(tile_entity)
+                .insert(SecretFade { target });
+        }
+    };
+
+    let translation = position.position.translation;
+    let player_tile = coords.physics_to_tile(Vec2::new(
+        translation.x,
+        translation.y,
+    ));
+    if let (Some(region), Some(player_tile)) =
+        (&revealed.0, player_tile)
+    {
+        if region.contains(&player_tile) {
+            return;
+        }
+    }
+
+    if let Some(region) = revealed.0.take() {
+        for tile_pos in region.iter() {
+            fade(tile_pos, opacity);
+        }
+    }
+    if let Some(player_tile) =
+        player_tile.filter(|tile_pos| is_secret(*tile_pos))
+    {
+        let region = flood_fill(player_tile, is_secret);
+        for tile_pos in region.iter() {
+            fade(tile_pos, 0.0);
+        }
+        revealed.0 = Some(region);
+    }
+}
+
+fn fade_secret_tiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<SecretConfig>,
+    mut tiles: Query<(
+        Entity,
+        &mut Tile,
+        &TileParent,
+        &SecretFade,
+    )>,
+    mut map_query: MapQuery,
+) {
+    let step = time.delta_seconds() / config.fade_time;
+    for (entity, mut tile, tile_parent, fade) in
+        tiles.iter_mut()
+    {
+        let alpha = tile.color.a();
+        let alpha = if fade.target > alpha {
+            (alpha + step).min(fade.target)
+        } else {
+            (alpha - step).max(fade.target)
+        };
+        tile.color.set_a(alpha);
+        map_query.notify_chunk(tile_parent.chunk);
+        if alpha == fade.target {
+            commands.entity(entity).remove::<SecretFade>();
+        }
+    }
+}
+
+/// The revealed tiles are despawned along with the map.
+fn forget_revealed_secret(
+    mut revealed: ResMut<RevealedSecret>,
+) {
+    revealed.0 = None;
+}
+
+/// A respawned level, such as after the map was reloaded,
+/// has all of its overlay tiles opaque again.
+fn forget_revealed_secret_on_respawn(
+    mut level_spawned: EventReader<LdtkLevelSpawned>,
+    mut revealed: ResMut<RevealedSecret>,
+) {
+    if level_spawned.iter().next().is_some() {
+        revealed.0 = None;
+    }
+}
+
+pub struct SecretPlugin;
+
+impl Plugin for SecretPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SecretConfig>()
+            .init_resource::<RevealedSecret>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        forget_revealed_secret_on_respawn
+                            .system()
+                            .before("reveal_secrets"),
+                    )
+                    .with_system(
+                        reveal_secrets
+                            .system()
+                            .label("reveal_secrets"),
+                    )
+                    .with_system(
+                        fade_secret_tiles.system(),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(
+                        forget_revealed_secret.system(),
+                    ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(positions: &[(u32, u32)]) -> HashSet<UVec2> {
+        positions
+            .iter()
+            .map(|(x, y)| UVec2::new(*x, *y))
+            .collect()
+    }
+
+    #[test]
+    fn fills_region_at_the_layer_edge() {
+        let region =
+            flood_fill(UVec2::new(1, 1), |tile_pos| {
+                tile_pos.x < 3 && tile_pos.y < 2
+            });
+        assert_eq!(
+            region,
+            tiles(&[
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn stops_at_a_ladder() {
+        // a row of overlay tiles with a ladder at x = 2
+        let is_secret = |tile_pos: UVec2| {
+            tile_pos.y == 0
+                && tile_pos.x < 5
+                && tile_pos.x != 2
+        };
+        assert_eq!(
+            flood_fill(UVec2::new(0, 0), is_secret),
+            tiles(&[(0, 0), (1, 0)])
+        );
+        assert_eq!(
+            flood_fill(UVec2::new(4, 0), is_secret),
+            tiles(&[(3, 0), (4, 0)])
+        );
+    }
+
+    #[test]
+    fn fills_a_single_tile() {
+        let start = UVec2::new(4, 4);
+        assert_eq!(
+            flood_fill(start, |tile_pos| tile_pos == start),
+            tiles(&[(4, 4)])
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    state::GameState,
+    tile_animation::TileSwap,
+    tile_behaviour::{
+        SpringStrength, TileBehaviourTable, TileBehaviours,
+    },
+    Player,
+};
+
+/// A spring that launches the corgi upwards when it lands
+/// on it, with the strength set in the tile behaviour table.
+pub struct Spring {
+    strength: SpringStrength,
+    /// Time left before the spring can launch again.
+    cooldown_timer: f32,
+    /// Whether the corgi was touching the spring last
+    /// frame, so it is only launched once per contact.
+    touching: bool,
+}
+
+impl Spring {
+    pub fn new(strength: SpringStrength) -> Self {
+        Self {
+            strength,
+            cooldown_timer: 0.0,
+            touching: false,
+        }
+    }
+
+    /// Advances the cooldown by a frame and returns whether
+    /// the corgi is launched, which is when it starts
+    /// touching the spring after the cooldown has run out.
+    fn update(
+        &mut self,
+        touching: bool,
+        delta: f32,
+    ) -> bool {
+        self.cooldown_timer -= delta;
+        let started_touching = touching && !self.touching;
+        self.touching = touching;
+        if !started_touching || self.cooldown_timer > 0.0 {
+            return false;
+        }
+        self.cooldown_timer = self.strength.cooldown;
+        true
+    }
+}
+
+/// Launches the corgi when it starts touching a spring,
+/// compressing the spring for its cooldown.
+#[allow(clippy::type_complexity)]
+fn launch_from_springs(
+    mut commands: Commands,
+    time: Res<Time>,
+    narrow_phase: Res<NarrowPhase>,
+    tile_behaviour_table: Res<TileBehaviourTable>,
+    tile_behaviours: Res<Assets<TileBehaviours>>,
+    mut player: Query<
+        (Entity, &mut RigidBodyVelocity),
+        With<Player>,
+    >,
+    mut springs: Query<(
+        Entity,
+        &mut Spring,
+        &Tile,
+        Option<&TileSwap>,
+    )>,
+) {
+    let (player, mut velocity) = match player.single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let delta = time.delta_seconds();
+
+    for (entity, mut spring, tile, swap) in
+        springs.iter_mut()
+    {
+        let touching = narrow_phase.intersection_pair(
+            player.handle(),
+            entity.handle(),
+        ) == Some(true);
+        if !spring.update(touching, delta) {
+            continue;
+        }
+
+        // the same height however fast the corgi was
+        // falling
+        velocity.linvel.y = spring.strength.launch_velocity;
+
+        // a swap that is still running would take the
+        // activated texture for the tile's own
+        if swap.is_some() {
+            continue;
+        }
+        if let Some(activated) = tile_behaviours
+            .get(&tile_behaviour_table.0)
+            .and_then(|tile_behaviours| {
+                tile_behaviours.get(tile.texture_index)
+            })
+            .and_then(|definition| definition.activated)
+        {
+            commands.entity(entity).insert(TileSwap::new(
+                activated,
+                spring.strength.cooldown,
+            ));
+        }
+    }
+}
+
+pub struct SpringPlugin;
+
+impl Plugin for SpringPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(launch_from_springs.system()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f32 = 0.1;
+
+    /// Feeds one contact state per frame to a spring with a
+    /// cooldown of three frames and a half, returning the
+    /// frames it launched on.
+    fn launches(frames: &str) -> Vec<usize> {
+        let mut spring = Spring::new(SpringStrength {
+            launch_velocity: 30.0,
+            cooldown: 3.5 * FRAME,
+        });
+        frames
+            .chars()
+            .enumerate()
+            .filter(|(_, touching)| {
+                spring.update(*touching == '#', FRAME)
+            })
+            .map(|(frame, _)| frame)
+            .collect()
+    }
+
+    #[test]
+    fn launches_once_per_contact() {
+        assert_eq!(launches("_######_"), vec![1]);
+        assert_eq!(launches("##______"), vec![0]);
+    }
+
+    #[test]
+    fn waits_for_the_cooldown() {
+        // bouncing straight back onto the spring
+        assert_eq!(launches("#_#_#_#_"), vec![0, 4]);
+        assert_eq!(launches("#____#__#"), vec![0, 5]);
+    }
+
+    #[test]
+    fn contact_during_the_cooldown_is_not_launched_later() {
+        // still touching when the cooldown runs out
+        assert_eq!(launches("#_####_#"), vec![0, 7]);
+    }
+}
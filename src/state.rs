@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::input::{clear_actions, Action};
+
+/// The top level flow of the game.
+///
+/// `Paused` is pushed on top of `Playing` so the level is
+/// kept while the pause screen is up, every other
+/// transition replaces the current state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Title,
+    Playing,
+    Paused,
+    LevelComplete,
+    GameOver,
+}
+
+fn resume_physics(
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn pause_physics(
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn pause(
+    mut actions: ResMut<Input<Action>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if actions.just_pressed(Action::Pause)
+        && state.push(GameState::Paused).is_ok()
+    {
+        // the pause screen also listens for pause, don't
+        // let it see this press in the same frame.
+        actions.reset(Action::Pause);
+    }
+}
+
+/// Adds `State<GameState>` and only steps the physics
+/// pipeline while `Playing`.
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_state(GameState::Title)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(resume_physics.system())
+                    .with_system(clear_actions.system()),
+            )
+            .add_system_set(
+                SystemSet::on_resume(GameState::Playing)
+                    .with_system(resume_physics.system())
+                    .with_system(clear_actions.system()),
+            )
+            .add_system_set(
+                SystemSet::on_pause(GameState::Playing)
+                    .with_system(pause_physics.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(pause_physics.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(pause.system()),
+            );
+    }
+}
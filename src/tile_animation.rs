@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::state::GameState;
+
+/// Shows another texture on a tile for a while, then
+/// switches back to the texture the tile had before, such
+/// as a spring that stays compressed for a moment after
+/// launching the corgi.
+pub struct TileSwap {
+    texture_index: u16,
+    timer: Timer,
+    /// The tile's own texture, once it has been swapped
+    /// out.
+    original: Option<u16>,
+}
+
+impl TileSwap {
+    pub fn new(texture_index: u16, seconds: f32) -> Self {
+        Self {
+            texture_index,
+            timer: Timer::from_seconds(seconds, false),
+            original: None,
+        }
+    }
+}
+
+fn swap_tiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tiles: Query<(
+        Entity,
+        &mut Tile,
+        &TileParent,
+        &mut TileSwap,
+    )>,
+    mut map_query: MapQuery,
+) {
+    for (entity, mut tile, tile_parent, mut swap) in
+        tiles.iter_mut()
+    {
+        let original = match swap.original {
+            Some(original) => original,
+            None => {
+                let original = tile.texture_index;
+                swap.original = Some(original);
+                tile.texture_index = swap.texture_index;
+                map_query.notify_chunk(tile_parent.chunk);
+                original
+            }
+        };
+        if swap.timer.tick(time.delta()).finished() {
+            tile.texture_index = original;
+            map_query.notify_chunk(tile_parent.chunk);
+            commands.entity(entity).remove::<TileSwap>();
+        }
+    }
+}
+
+pub struct TileAnimationPlugin;
+
+impl Plugin for TileAnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(swap_tiles.system()),
+        );
+    }
+}
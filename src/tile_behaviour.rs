@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{
+        AssetLoader, BoxedFuture, LoadContext, LoadedAsset,
+    },
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::Deserialize;
+
+/// What a tile does to the corgi.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize,
+)]
+pub enum TileBehaviour {
+    Solid,
+    Spike,
+    Spring,
+    OneWay,
+    Ladder,
+    Checkpoint,
+    Crumbling,
+}
+
+/// Collider shape for a tile, in pixels, relative to the
+/// center of the tile.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TileCollider {
+    pub half_extents: Vec2,
+    #[serde(default)]
+    pub offset: Vec2,
+}
+
+/// How a spring tile launches the corgi.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SpringStrength {
+    /// Upwards speed, in physics units per second.
+    pub launch_velocity: f32,
+    /// Seconds before the spring can launch again.
+    pub cooldown: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileDefinition {
+    pub behaviour: TileBehaviour,
+    /// If no collider is given the tile gets a collider
+    /// covering the whole tile.
+    #[serde(default)]
+    pub collider: Option<TileCollider>,
+    /// Texture index to switch to once the tile has been
+    /// activated, such as a pressed checkpoint.
+    #[serde(default)]
+    pub activated: Option<u16>,
+    /// Required for `Spring` tiles.
+    #[serde(default)]
+    pub spring: Option<SpringStrength>,
+}
+
+/// Maps tileset texture indices to tile behaviours.
+///
+/// Loaded from `*.tiles.ron` files that sit next to the
+/// tileset image, so repacking the tileset only means
+/// updating the table.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "6b7a4c1e-2f0d-4a8e-9c3b-5d1e7f2a9b40"]
+pub struct TileBehaviours {
+    pub tiles: HashMap<u16, TileDefinition>,
+}
+
+impl TileBehaviours {
+    pub fn get(
+        &self,
+        texture_index: u16,
+    ) -> Option<&TileDefinition> {
+        self.tiles.get(&texture_index)
+    }
+}
+
+/// The behaviour table used by the current level.
+pub struct TileBehaviourTable(pub Handle<TileBehaviours>);
+
+#[derive(Default)]
+pub struct TileBehavioursLoader;
+
+impl AssetLoader for TileBehavioursLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let behaviours: TileBehaviours =
+                ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(
+                LoadedAsset::new(behaviours),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["tiles.ron"];
+        EXTENSIONS
+    }
+}
+
+pub struct TileBehaviourPlugin;
+
+impl Plugin for TileBehaviourPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<TileBehaviours>()
+            .init_asset_loader::<TileBehavioursLoader>();
+    }
+}